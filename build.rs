@@ -0,0 +1,80 @@
+//! Records the build metadata reported by the version endpoint as compile-time environment
+//! variables. Values which cannot be determined, such as the commit of a source tarball, are
+//! reported as `unknown`.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // The sources, so the dirty flag and the timestamp follow the edits which are not staged
+    for path in ["build.rs", "Cargo.toml", "src", "migrations", "locales"] {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+    rerun_if_git_changed();
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = command_output("git", &["rev-parse", "HEAD"]);
+    let dirty = command_output("git", &["status", "--porcelain", "--untracked-files=no"])
+        .map(|status| !status.is_empty());
+    let rustc = env::var("RUSTC").unwrap_or("rustc".to_string());
+
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", commit.unwrap_or("unknown".to_string()));
+    println!(
+        "cargo:rustc-env=BUILD_GIT_DIRTY={}",
+        dirty.map_or("unknown".to_string(), |dirty| dirty.to_string())
+    );
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", build_timestamp());
+    println!(
+        "cargo:rustc-env=BUILD_RUSTC_VERSION={}",
+        command_output(&rustc, &["--version"]).unwrap_or("unknown".to_string())
+    );
+    println!("cargo:rustc-env=BUILD_CARGO_FEATURES={}", cargo_features().join(","));
+}
+
+/// Rerun when the commit checked out changes: the `HEAD`, the branch it points to, which may be
+/// packed, and the index. Missing files are left out, as cargo would rerun on every build.
+fn rerun_if_git_changed() {
+    let Some(git_dir) = command_output("git", &["rev-parse", "--git-dir"]).map(PathBuf::from) else {
+        return;
+    };
+    let mut paths = vec![git_dir.join("HEAD"), git_dir.join("index"), git_dir.join("packed-refs")];
+    if let Some(reference) = command_output("git", &["symbolic-ref", "-q", "HEAD"]) {
+        paths.push(git_dir.join(reference));
+    }
+    for path in paths.iter().filter(|path| path.exists()) {
+        println!("cargo:rerun-if-changed={}", path.display());
+    }
+}
+
+/// Trimmed standard output of a successful command
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok().map(|stdout| stdout.trim().to_string())
+}
+
+/// Seconds since the epoch, from `SOURCE_DATE_EPOCH` for reproducible builds
+fn build_timestamp() -> u64 {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        })
+}
+
+/// Features enabled for the build, as named in `Cargo.toml`
+fn cargo_features() -> Vec<String> {
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(|feature| feature.to_ascii_lowercase()))
+        .map(|feature| feature.replace('_', "-"))
+        .collect();
+    features.sort();
+    features
+}
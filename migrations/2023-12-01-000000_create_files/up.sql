@@ -0,0 +1,19 @@
+CREATE TABLE files (
+    id UUID PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    content_type TEXT NOT NULL,
+    file_name TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX files_owner_id_idx ON files (owner_id);
+
+CREATE TABLE storage_usage (
+    owner_id TEXT PRIMARY KEY,
+    bytes_used BIGINT NOT NULL DEFAULT 0,
+    object_count BIGINT NOT NULL DEFAULT 0,
+    quota_bytes BIGINT,
+    quota_objects BIGINT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+DROP INDEX files_upload_url_expires_at_idx;
+ALTER TABLE files DROP COLUMN upload_url_expires_at;
+ALTER TABLE files DROP COLUMN status;
@@ -0,0 +1,7 @@
+-- Presigned uploads are `pending` until the client confirms the object was uploaded. A presigned
+-- upload deleted while its URL is valid stays `deleted` until the URL expires, so an object put
+-- again through the URL is still accounted and then removed.
+ALTER TABLE files ADD COLUMN status TEXT NOT NULL DEFAULT 'stored';
+ALTER TABLE files ADD COLUMN upload_url_expires_at TIMESTAMPTZ;
+
+CREATE INDEX files_upload_url_expires_at_idx ON files (upload_url_expires_at) WHERE status <> 'stored';
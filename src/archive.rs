@@ -0,0 +1,185 @@
+//! ZIP archives of stored files, built on the fly from the storage backend
+
+use std::collections::HashSet;
+use std::io;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use log::{debug, error};
+use tokio::sync::mpsc;
+
+use crate::constants::archive_constants::*;
+use crate::s3_client::client::S3Client;
+use crate::util::zip_stream::{ZipWriter, MAX_ARCHIVE_SIZE, MAX_ENTRY_NAME_LEN};
+use crate::util::env::env_parse;
+
+/// Limits applied to archive downloads
+#[derive(Debug, Clone)]
+pub struct ArchivePolicy {
+    pub max_total_bytes: u64,
+    pub max_files: usize,
+}
+
+impl ArchivePolicy {
+    /// Read the limits from the environment. The total size is capped to what a ZIP without
+    /// ZIP64 extensions can hold.
+    pub fn from_env() -> Self {
+        let max_total_bytes = env_parse(ARCHIVE_MAX_TOTAL_BYTES_ENV, DEFAULT_ARCHIVE_MAX_TOTAL_BYTES);
+        let max_files = env_parse(ARCHIVE_MAX_FILES_ENV, DEFAULT_ARCHIVE_MAX_FILES);
+
+        ArchivePolicy {
+            max_total_bytes: max_total_bytes.min(MAX_ARCHIVE_SIZE),
+            max_files: max_files.min(u16::MAX as usize),
+        }
+    }
+}
+
+/// A stored object added to an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub key: String,
+    pub name: String,
+    pub modified: DateTime<Utc>,
+}
+
+/// Make `names` usable as archive entry names: path separators are replaced so every entry
+/// lands at the root, and duplicates get a ` (n)` suffix before their extension. Names are
+/// truncated to the [`MAX_ENTRY_NAME_LEN`] bytes a ZIP header can record.
+pub fn unique_entry_names(names: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut used = HashSet::new();
+    names
+        .into_iter()
+        .map(|name| {
+            let name: String = name
+                .chars()
+                .map(|c| if matches!(c, '/' | '\\') || c.is_control() { '_' } else { c })
+                .collect();
+            let name = match name.trim_matches('.') {
+                "" => "file".to_string(),
+                _ => name,
+            };
+
+            let (stem, extension) = match name.rfind('.') {
+                Some(dot) if dot > 0 => name.split_at(dot),
+                _ => (name.as_str(), ""),
+            };
+            let mut candidate = entry_name(stem, extension);
+            let mut n = 1;
+            while !used.insert(candidate.clone()) {
+                candidate = entry_name(stem, &format!(" ({}){}", n, extension));
+                n += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// `stem` followed by `suffix`, the stem being shortened first when the name is too long
+fn entry_name(stem: &str, suffix: &str) -> String {
+    let stem = truncate(stem, MAX_ENTRY_NAME_LEN.saturating_sub(suffix.len()));
+    truncate(&format!("{}{}", stem, suffix), MAX_ENTRY_NAME_LEN).to_string()
+}
+
+/// Longest prefix of `s` of at most `max_len` bytes which ends on a character boundary
+fn truncate(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let end = (0..=max_len).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0);
+    &s[..end]
+}
+
+/// Stream a ZIP archive of `entries`.
+///
+/// Objects are fetched one after the other and forwarded chunk by chunk through a bounded
+/// channel, so at most a few chunks are held in memory whatever the size of the objects.
+pub fn stream_archive(s3_client: S3Client, entries: Vec<ArchiveEntry>) -> BoxStream<'static, io::Result<Bytes>> {
+    let (tx, rx) = mpsc::channel(ARCHIVE_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        match write_archive(&s3_client, entries, &tx).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                debug!("archive download aborted by the client");
+            }
+            Err(err) => {
+                error!("failed to stream archive: {}", err);
+                let _ = tx.send(Err(err)).await;
+            }
+        }
+    });
+
+    stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|item| (item, rx)) }).boxed()
+}
+
+async fn write_archive(
+    s3_client: &S3Client,
+    entries: Vec<ArchiveEntry>,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+) -> io::Result<()> {
+    let mut writer = ZipWriter::new();
+    for entry in entries {
+        send(tx, writer.start_entry(&entry.name, entry.modified)).await?;
+
+        let mut body = s3_client
+            .get_object(&entry.key, None, None)
+            .await
+            .map_err(io::Error::other)?
+            .body;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size: u64 = 0;
+        while let Some(chunk) = body.try_next().await.map_err(io::Error::other)? {
+            size += chunk.len() as u64;
+            if writer.offset() + size > MAX_ARCHIVE_SIZE {
+                return Err(io::Error::other("the archive exceeds the maximum ZIP size"));
+            }
+            hasher.update(&chunk);
+            send(tx, chunk).await?;
+        }
+
+        send(tx, writer.finish_entry(hasher.finalize(), size)).await?;
+    }
+    send(tx, writer.finish()).await
+}
+
+async fn send(tx: &mpsc::Sender<io::Result<Bytes>>, chunk: Bytes) -> io::Result<()> {
+    tx.send(Ok(chunk))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the client disconnected"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        unique_entry_names(names.iter().map(|name| name.to_string()))
+    }
+
+    #[test]
+    fn test_unique_entry_names() {
+        assert_eq!(
+            names(&["a.txt", "a.txt", "b", "a.txt", "b"]),
+            vec!["a.txt", "a (1).txt", "b", "a (2).txt", "b (1)"]
+        );
+    }
+
+    #[test]
+    fn test_sanitized_entry_names() {
+        assert_eq!(
+            names(&["../../etc/passwd", "dir\\file.txt", "..", ".hidden", "line\nbreak"]),
+            vec![".._.._etc_passwd", "dir_file.txt", "file", ".hidden", "line_break"]
+        );
+    }
+
+    #[test]
+    fn test_long_entry_names() {
+        let long = format!("{}.txt", "é".repeat(MAX_ENTRY_NAME_LEN));
+        let names = unique_entry_names(vec![long.clone(), long]);
+
+        assert!(names.iter().all(|name| name.len() <= MAX_ENTRY_NAME_LEN));
+        assert!(names[0].ends_with("é.txt"));
+        assert!(names[1].ends_with("é (1).txt"));
+        assert_ne!(names[0], names[1]);
+    }
+}
@@ -1,3 +1,3 @@
-mod role;
+pub mod role;
 mod permission;
-mod jwt;
\ No newline at end of file
+pub mod jwt;
\ No newline at end of file
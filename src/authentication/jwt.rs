@@ -1,18 +1,22 @@
 use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::errors::ErrorKind;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 use anyhow::{Result, Error};
 use crate::constants::jwt_constants::JWT_SECRET;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Claims {
-    sub: String,
-    aud: Vec<String>,
-    role: String,
-    exp: u64,
-    nbf: Option<u64>,
-    iat: u64,
-    jti: uuid::Uuid,
+    pub sub: String,
+    pub aud: Vec<String>,
+    pub role: String,
+    pub exp: u64,
+    pub nbf: Option<u64>,
+    pub iat: u64,
+    pub jti: uuid::Uuid,
+    /// Preferred locale of the user for the response messages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
 }
 
 pub fn new_jwt(subject: &str, role: &str, aud: Vec<String>, duration: u64) -> Result<String> {
@@ -33,6 +37,7 @@ pub fn new_jwt(subject: &str, role: &str, aud: Vec<String>, duration: u64) -> Re
         nbf: Option::from(current_time.as_secs()),
         iat: current_time.as_secs(),
         jti: uuid::Uuid::new_v4(),
+        locale: None,
     };
     let header = Header::new(Algorithm::HS512);
     return match encode(&header, &claim, &EncodingKey::from_secret(JWT_SECRET)) {
@@ -62,6 +67,22 @@ pub fn decode_jwt(jwt: &str, aud: Vec<String>) -> Result<Claims> {
     claim
 }
 
+/// Reason a token was rejected by [`decode_jwt`], as reported by the metrics
+pub fn failure_reason(err: &Error) -> &'static str {
+    let Some(err) = err.downcast_ref::<jsonwebtoken::errors::Error>() else {
+        return "invalid";
+    };
+    match err.kind() {
+        ErrorKind::ExpiredSignature => "expired",
+        ErrorKind::ImmatureSignature => "not_yet_valid",
+        ErrorKind::InvalidSignature => "invalid_signature",
+        ErrorKind::InvalidAudience => "invalid_audience",
+        ErrorKind::InvalidAlgorithm => "invalid_algorithm",
+        ErrorKind::InvalidToken | ErrorKind::Base64(_) | ErrorKind::Json(_) | ErrorKind::Utf8(_) => "malformed",
+        _ => "invalid",
+    }
+}
+
 
 
 #[cfg(test)]
@@ -111,4 +132,18 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_failure_reason() {
+        let aud = vec![String::from("test_api")];
+        let token = new_jwt("tripg", "test_api", aud.clone(), 3000).unwrap();
+
+        let err = decode_jwt(&token, vec![String::from("other_api")]).unwrap_err();
+        assert_eq!(failure_reason(&err), "invalid_audience");
+        let err = decode_jwt(&format!("{}x", token), aud.clone()).unwrap_err();
+        assert_eq!(failure_reason(&err), "invalid_signature");
+        let err = decode_jwt("not-a-token", aud).unwrap_err();
+        assert_eq!(failure_reason(&err), "malformed");
+        assert_eq!(failure_reason(&anyhow::anyhow!("clock error")), "invalid");
+    }
+
 }
\ No newline at end of file
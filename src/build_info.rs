@@ -0,0 +1,82 @@
+//! Build and runtime information reported by the version endpoint
+//!
+//! The build metadata is recorded by the build script; see `build.rs`.
+
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+static STARTED_AT: Lazy<(Instant, DateTime<Utc>)> = Lazy::new(|| (Instant::now(), Utc::now()));
+
+/// Record the start of the process, from which the uptime is measured
+pub fn mark_started() {
+    Lazy::force(&STARTED_AT);
+}
+
+pub fn uptime() -> Duration {
+    STARTED_AT.0.elapsed()
+}
+
+/// Data of the version response
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// `unknown` when built outside of a git checkout
+    pub git_commit: &'static str,
+    /// Whether tracked files had uncommitted changes, unknown outside of a git checkout
+    pub git_dirty: Option<bool>,
+    pub build_timestamp: DateTime<Utc>,
+    pub rustc_version: &'static str,
+    pub cargo_features: Vec<&'static str>,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    /// Latest migration applied to the database, unknown when it is unavailable
+    pub migration_version: Option<String>,
+}
+
+impl BuildInfo {
+    pub fn new(migration_version: Option<String>) -> Self {
+        BuildInfo {
+            version: VERSION,
+            git_commit: env!("BUILD_GIT_COMMIT"),
+            git_dirty: env!("BUILD_GIT_DIRTY").parse().ok(),
+            build_timestamp: env!("BUILD_TIMESTAMP")
+                .parse()
+                .ok()
+                .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+                .unwrap_or_default(),
+            rustc_version: env!("BUILD_RUSTC_VERSION"),
+            cargo_features: env!("BUILD_CARGO_FEATURES")
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .collect(),
+            started_at: STARTED_AT.1,
+            uptime_secs: uptime().as_secs(),
+            migration_version,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        mark_started();
+        let info = BuildInfo::new(Some("20231201000000".to_string()));
+
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.rustc_version.starts_with("rustc ") || info.rustc_version == "unknown");
+        assert!(info.build_timestamp > DateTime::<Utc>::default());
+        assert!(info.started_at <= Utc::now());
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["migration_version"], "20231201000000");
+        assert!(json["cargo_features"].is_array());
+    }
+}
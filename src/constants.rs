@@ -1 +1,11 @@
-pub mod jwt_constants;
\ No newline at end of file
+pub mod archive_constants;
+pub mod database_constants;
+pub mod health_constants;
+pub mod jwt_constants;
+pub mod logging_constants;
+pub mod metrics_constants;
+pub mod quota_constants;
+pub mod request_constants;
+pub mod s3_constants;
+pub mod upload_constants;
+pub mod variant_constants;
@@ -0,0 +1,9 @@
+pub const ARCHIVE_MAX_TOTAL_BYTES_ENV: &str = "ARCHIVE_MAX_TOTAL_BYTES";
+pub const ARCHIVE_MAX_FILES_ENV: &str = "ARCHIVE_MAX_FILES";
+
+pub const DEFAULT_ARCHIVE_MAX_TOTAL_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+pub const DEFAULT_ARCHIVE_MAX_FILES: usize = 1000;
+pub const DEFAULT_ARCHIVE_NAME: &str = "archive";
+
+/// Number of chunks buffered between the object store and the client
+pub const ARCHIVE_CHANNEL_CAPACITY: usize = 8;
@@ -0,0 +1,4 @@
+pub const HEALTH_CHECK_TIMEOUT_MS_ENV: &str = "HEALTH_CHECK_TIMEOUT_MS";
+
+/// Time a dependency has to answer before it is reported down
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT_MS: u64 = 2000;
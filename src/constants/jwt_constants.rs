@@ -1,2 +1,3 @@
-const BEARER: &str = "Bearer ";
-pub const JWT_SECRET: &[u8] = b"secret";
\ No newline at end of file
+pub const BEARER: &str = "Bearer ";
+pub const JWT_SECRET: &[u8] = b"secret";
+pub const JWT_AUDIENCE: &str = "axum_api";
\ No newline at end of file
@@ -0,0 +1,69 @@
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client supplied request ID which is propagated, longer ones are replaced
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Log target of the HTTP access events, so they can be filtered apart from the application logs
+pub const ACCESS_LOG_TARGET: &str = "access";
+
+/// Comma-separated field names or paths masked in the log events, on top of the defaults
+pub const LOG_REDACTED_FIELDS_ENV: &str = "LOG_REDACTED_FIELDS";
+pub const DEFAULT_LOG_REDACTED_FIELDS: &str =
+    "authorization,cookie,set-cookie,password,secret,token,access_token,refresh_token,x-amz-security-token";
+
+/// Replacement of the redacted values
+pub const REDACTED: &str = "[REDACTED]";
+
+pub const LOG_BUFFER_CAPACITY_ENV: &str = "LOG_BUFFER_CAPACITY";
+/// `block` to wait for room in the buffer, or `drop` to discard the events which do not fit
+pub const LOG_OVERFLOW_ENV: &str = "LOG_OVERFLOW";
+/// Comma-separated sinks the events are written to: `stderr`, `file` and `syslog`
+pub const LOG_SINKS_ENV: &str = "LOG_SINKS";
+pub const LOG_FILE_PATH_ENV: &str = "LOG_FILE_PATH";
+/// Size a log file is rotated at, `0` to only rotate it on schedule
+pub const LOG_FILE_MAX_BYTES_ENV: &str = "LOG_FILE_MAX_BYTES";
+/// `hourly`, `daily` or `never`
+pub const LOG_FILE_ROTATION_ENV: &str = "LOG_FILE_ROTATION";
+/// Number of rotated files kept
+pub const LOG_FILE_MAX_FILES_ENV: &str = "LOG_FILE_MAX_FILES";
+pub const LOG_SYSLOG_ADDR_ENV: &str = "LOG_SYSLOG_ADDR";
+pub const LOG_SYSLOG_APP_NAME_ENV: &str = "LOG_SYSLOG_APP_NAME";
+
+pub const DEFAULT_LOG_BUFFER_CAPACITY: usize = 8192;
+pub const DEFAULT_LOG_OVERFLOW: &str = "block";
+pub const DEFAULT_LOG_SINKS: &str = "stderr";
+pub const DEFAULT_LOG_FILE_PATH: &str = "logs/axum_api.log";
+pub const DEFAULT_LOG_FILE_MAX_BYTES: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_LOG_FILE_ROTATION: &str = "daily";
+pub const DEFAULT_LOG_FILE_MAX_FILES: usize = 7;
+pub const DEFAULT_LOG_SYSLOG_ADDR: &str = "127.0.0.1:514";
+pub const DEFAULT_LOG_SYSLOG_APP_NAME: &str = "axum_api";
+
+/// Log target of the audit events, such as the changes of the log level
+pub const AUDIT_LOG_TARGET: &str = "audit";
+
+/// Initial level directives, in the `RUST_LOG` syntax of env_logger
+pub const LOG_LEVEL_ENV: &str = "RUST_LOG";
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+/// Longest delay after which a runtime change of the log level can be reverted
+pub const MAX_LOG_LEVEL_REVERT_SECS: u64 = 24 * 60 * 60;
+
+/// Comma-separated `level=rate` sample rates, such as `debug=0.1`; unlisted levels are all kept
+pub const LOG_SAMPLE_RATES_ENV: &str = "LOG_SAMPLE_RATES";
+/// Whether the ERROR events are sampled and rate limited like the others
+pub const LOG_SAMPLE_ERRORS_ENV: &str = "LOG_SAMPLE_ERRORS";
+/// Events a call site can log in a burst, `0` to disable the rate limiting
+pub const LOG_RATE_LIMIT_BURST_ENV: &str = "LOG_RATE_LIMIT_BURST";
+/// Events a call site can log per second once its burst is spent
+pub const LOG_RATE_LIMIT_PER_SEC_ENV: &str = "LOG_RATE_LIMIT_PER_SEC";
+/// Interval of the summaries of the suppressed events
+pub const LOG_SUPPRESSED_SUMMARY_SECS_ENV: &str = "LOG_SUPPRESSED_SUMMARY_SECS";
+
+pub const DEFAULT_LOG_RATE_LIMIT_BURST: u32 = 100;
+pub const DEFAULT_LOG_RATE_LIMIT_PER_SEC: f64 = 10.0;
+pub const DEFAULT_LOG_SUPPRESSED_SUMMARY_SECS: u64 = 60;
+
+/// Name of the service in the log events, the crate name when unset
+pub const SERVICE_NAME_ENV: &str = "SERVICE_NAME";
+/// Deployment environment in the log events, such as `production`
+pub const SERVICE_ENVIRONMENT_ENV: &str = "SERVICE_ENVIRONMENT";
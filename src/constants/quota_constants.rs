@@ -0,0 +1,5 @@
+pub const QUOTA_DEFAULT_BYTES_ENV: &str = "QUOTA_DEFAULT_BYTES";
+pub const QUOTA_DEFAULT_OBJECTS_ENV: &str = "QUOTA_DEFAULT_OBJECTS";
+
+pub const DEFAULT_QUOTA_BYTES: i64 = 5 * 1024 * 1024 * 1024;
+pub const DEFAULT_QUOTA_OBJECTS: i64 = 10_000;
@@ -0,0 +1,3 @@
+/// Largest request body the extractors buffer, such as the JSON, MessagePack and CBOR bodies.
+/// Streamed uploads have limits of their own.
+pub const MAX_BUFFERED_BODY_BYTES: usize = 2 * 1024 * 1024;
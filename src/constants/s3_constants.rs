@@ -0,0 +1,11 @@
+pub const S3_ENDPOINT_ENV: &str = "S3_ENDPOINT";
+pub const S3_ACCESS_KEY_ENV: &str = "S3_ACCESS_KEY";
+pub const S3_SECRET_KEY_ENV: &str = "S3_SECRET_KEY";
+pub const S3_REGION_ENV: &str = "S3_REGION";
+pub const S3_BUCKET_ENV: &str = "S3_BUCKET";
+
+pub const DEFAULT_S3_ENDPOINT: &str = "http://127.0.0.1:9000";
+pub const DEFAULT_S3_ACCESS_KEY: &str = "minioadmin";
+pub const DEFAULT_S3_SECRET_KEY: &str = "minioadmin";
+pub const DEFAULT_S3_REGION: &str = "us-east-1";
+pub const DEFAULT_S3_BUCKET: &str = "files";
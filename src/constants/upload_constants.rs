@@ -0,0 +1,27 @@
+pub const UPLOAD_ALLOWED_MIME_TYPES_ENV: &str = "UPLOAD_ALLOWED_MIME_TYPES";
+pub const UPLOAD_MAX_SIZE_USER_ENV: &str = "UPLOAD_MAX_SIZE_USER";
+pub const UPLOAD_MAX_SIZE_ADMIN_ENV: &str = "UPLOAD_MAX_SIZE_ADMIN";
+pub const UPLOAD_TMP_DIR_ENV: &str = "UPLOAD_TMP_DIR";
+pub const PRESIGNED_UPLOAD_EXPIRY_SECS_ENV: &str = "PRESIGNED_UPLOAD_EXPIRY_SECS";
+
+pub const DEFAULT_UPLOAD_ALLOWED_MIME_TYPES: &str = "image/*,video/mp4,application/pdf,text/plain";
+pub const DEFAULT_UPLOAD_MAX_SIZE_USER: u64 = 100 * 1024 * 1024;
+pub const DEFAULT_UPLOAD_MAX_SIZE_ADMIN: u64 = 1024 * 1024 * 1024;
+pub const DEFAULT_PRESIGNED_UPLOAD_EXPIRY_SECS: u64 = 900;
+
+/// Period of the release of the presigned uploads which expired without being confirmed
+pub const PENDING_UPLOAD_SWEEP_INTERVAL_SECS: u64 = 60;
+/// Time given to an upload started just before its URL expired to finish before it is swept
+pub const PENDING_UPLOAD_SWEEP_GRACE_SECS: i64 = 300;
+/// Expired uploads released per sweep, the rest waiting for the next one
+pub const PENDING_UPLOAD_SWEEP_BATCH_SIZE: i64 = 100;
+
+pub const CONTENT_MD5_HEADER: &str = "content-md5";
+pub const CONTENT_SHA256_HEADER: &str = "x-content-sha256";
+pub const FILE_NAME_HEADER: &str = "x-file-name";
+
+/// Longest file name, in characters, recorded for an upload
+pub const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Number of leading bytes kept in memory for content sniffing
+pub const SNIFF_PREFIX_LEN: usize = 8192;
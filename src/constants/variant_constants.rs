@@ -0,0 +1,10 @@
+pub const IMAGE_VARIANTS_ENV: &str = "IMAGE_VARIANTS";
+pub const IMAGE_VARIANT_MAX_SOURCE_BYTES_ENV: &str = "IMAGE_VARIANT_MAX_SOURCE_BYTES";
+pub const IMAGE_VARIANT_CONCURRENCY_ENV: &str = "IMAGE_VARIANT_CONCURRENCY";
+
+/// Comma separated `name:WIDTHxHEIGHT:format` or `name:format` derivative specifications
+pub const DEFAULT_IMAGE_VARIANTS: &str = "thumbnail:128x128:webp,small:480x480:webp,large:1280x1280:jpeg";
+pub const DEFAULT_IMAGE_VARIANT_MAX_SOURCE_BYTES: u64 = 50 * 1024 * 1024;
+pub const DEFAULT_IMAGE_VARIANT_CONCURRENCY: usize = 2;
+
+pub const JPEG_QUALITY: u8 = 85;
@@ -0,0 +1,102 @@
+use std::env;
+use std::time::{Duration, Instant};
+
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool, PoolError};
+use diesel::sql_types::{Nullable, Text};
+use diesel::{QueryableByName, RunQueryDsl};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+use thiserror::Error;
+
+use crate::constants::database_constants::*;
+use crate::metrics::metrics;
+use crate::util::env::env_parse;
+
+pub mod models;
+pub mod schema;
+pub mod usage_repository;
+
+pub type DbPool = Pool<ConnectionManager<PgConnection>>;
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// Error returned by [`with_connection`]
+#[derive(Error, Debug)]
+pub enum DbError {
+    #[error("no database connection available: {0}")]
+    Pool(#[from] PoolError),
+
+    #[error("database query failed: {0}")]
+    Query(#[from] diesel::result::Error),
+
+    #[error("database task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// Build the connection pool from `DATABASE_URL`.
+///
+/// Connections are established lazily so the API starts even when the database is down.
+pub fn new_pool() -> DbPool {
+    let url = env::var(DATABASE_URL_ENV).unwrap_or(DEFAULT_DATABASE_URL.to_string());
+    let size = env_parse(DATABASE_POOL_SIZE_ENV, DEFAULT_DATABASE_POOL_SIZE);
+
+    Pool::builder()
+        .max_size(size)
+        .build_unchecked(ConnectionManager::<PgConnection>::new(url))
+}
+
+/// Apply the pending embedded migrations
+pub async fn run_migrations(pool: &DbPool) -> anyhow::Result<()> {
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get()?;
+        conn.run_pending_migrations(MIGRATIONS)
+            .map_err(|err| anyhow::anyhow!(err))?;
+        Ok(())
+    })
+    .await?
+}
+
+/// Version of the latest migration applied to the database, if any
+pub async fn applied_migration_version(pool: &DbPool) -> Result<Option<String>, DbError> {
+    #[derive(QueryableByName)]
+    struct LatestMigration {
+        #[diesel(sql_type = Nullable<Text>)]
+        version: Option<String>,
+    }
+
+    with_connection(pool, |conn| {
+        diesel::sql_query("SELECT MAX(version) AS version FROM __diesel_schema_migrations")
+            .get_result::<LatestMigration>(conn)
+            .map(|latest| latest.version)
+    })
+    .await
+}
+
+/// Run blocking diesel code on a pooled connection without stalling the async runtime, recording
+/// the latency of the queries
+pub async fn with_connection<F, T>(pool: &DbPool, f: F) -> Result<T, DbError>
+where
+    F: FnOnce(&mut PgConnection) -> diesel::QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    with_connection_timeout(pool, pool.connection_timeout(), f).await
+}
+
+/// [`with_connection`], waiting at most `timeout` for a connection of the pool rather than its
+/// configured connection timeout
+pub async fn with_connection_timeout<F, T>(pool: &DbPool, timeout: Duration, f: F) -> Result<T, DbError>
+where
+    F: FnOnce(&mut PgConnection) -> diesel::QueryResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get_timeout(timeout)?;
+        let start = Instant::now();
+        let result = f(&mut conn);
+        metrics().observe_db_query(start.elapsed(), result.is_ok());
+        Ok(result?)
+    })
+    .await?
+}
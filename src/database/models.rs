@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::database::schema::{files, storage_usage};
+
+/// A file stored in the storage backend, owned by a user or tenant
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = files)]
+pub struct StoredFile {
+    pub id: uuid::Uuid,
+    pub owner_id: String,
+    pub size_bytes: i64,
+    pub content_type: String,
+    pub file_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// One of the [`FileStatus`] names
+    pub status: String,
+    /// Expiry of the presigned URL the file was uploaded through, if any
+    pub upload_url_expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = files)]
+pub struct NewStoredFile {
+    pub id: uuid::Uuid,
+    pub owner_id: String,
+    pub size_bytes: i64,
+    pub content_type: String,
+    pub file_name: Option<String>,
+    pub status: String,
+    pub upload_url_expires_at: Option<DateTime<Utc>>,
+}
+
+/// Lifecycle of a file record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// Presigned upload not confirmed yet
+    Pending,
+    /// Object committed to the storage backend
+    Stored,
+    /// Presigned upload deleted while its URL is valid, kept accounted until the URL expires
+    Deleted,
+}
+
+impl FileStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileStatus::Pending => "pending",
+            FileStatus::Stored => "stored",
+            FileStatus::Deleted => "deleted",
+        }
+    }
+}
+
+/// Bytes and objects stored by an owner, with optional per-owner quota overrides
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = storage_usage)]
+pub struct StorageUsage {
+    pub owner_id: String,
+    pub bytes_used: i64,
+    pub object_count: i64,
+    pub quota_bytes: Option<i64>,
+    pub quota_objects: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+}
@@ -0,0 +1,205 @@
+//! Per-owner storage accounting, kept consistent with the `files` table
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use crate::database::models::{FileStatus, NewStoredFile, StorageUsage, StoredFile};
+use crate::database::schema::{files, storage_usage};
+use crate::upload::quota::QuotaPolicy;
+
+/// Outcome of [`reserve_file`]
+#[derive(Debug)]
+pub enum ReserveOutcome {
+    Reserved(StoredFile),
+    QuotaExceeded,
+}
+
+/// Record a new file and account its size to the owner, unless it would exceed the quota.
+///
+/// The usage row is locked for the duration of the transaction so concurrent uploads of the
+/// same owner cannot overshoot the quota together.
+pub fn reserve_file(
+    conn: &mut PgConnection,
+    new_file: NewStoredFile,
+    policy: &QuotaPolicy,
+) -> QueryResult<ReserveOutcome> {
+    conn.transaction(|conn| {
+        ensure_usage_row(conn, &new_file.owner_id)?;
+        let usage = storage_usage::table
+            .find(&new_file.owner_id)
+            .select(StorageUsage::as_select())
+            .for_update()
+            .first(conn)?;
+
+        if policy.would_exceed(&usage, new_file.size_bytes) {
+            return Ok(ReserveOutcome::QuotaExceeded);
+        }
+
+        diesel::update(storage_usage::table.find(&new_file.owner_id))
+            .set((
+                storage_usage::bytes_used.eq(storage_usage::bytes_used + new_file.size_bytes),
+                storage_usage::object_count.eq(storage_usage::object_count + 1),
+                storage_usage::updated_at.eq(diesel::dsl::now),
+            ))
+            .execute(conn)?;
+
+        let file = diesel::insert_into(files::table)
+            .values(&new_file)
+            .returning(StoredFile::as_returning())
+            .get_result(conn)?;
+
+        Ok(ReserveOutcome::Reserved(file))
+    })
+}
+
+/// Remove a file record and give its size back to the owner
+pub fn release_file(conn: &mut PgConnection, file_id: uuid::Uuid) -> QueryResult<Option<StoredFile>> {
+    conn.transaction(|conn| {
+        let file = diesel::delete(files::table.find(file_id))
+            .returning(StoredFile::as_returning())
+            .get_result(conn)
+            .optional()?;
+
+        if let Some(file) = &file {
+            diesel::update(storage_usage::table.find(&file.owner_id))
+                .set((
+                    storage_usage::bytes_used.eq(storage_usage::bytes_used - file.size_bytes),
+                    storage_usage::object_count.eq(storage_usage::object_count - 1),
+                    storage_usage::updated_at.eq(diesel::dsl::now),
+                ))
+                .execute(conn)?;
+        }
+
+        Ok(file)
+    })
+}
+
+/// Stored file `file_id`. Presigned uploads which were not confirmed yet, and deleted ones, are
+/// skipped.
+pub fn find_file(conn: &mut PgConnection, file_id: uuid::Uuid) -> QueryResult<Option<StoredFile>> {
+    files::table
+        .find(file_id)
+        .filter(files::status.eq(FileStatus::Stored.as_str()))
+        .select(StoredFile::as_select())
+        .first(conn)
+        .optional()
+}
+
+/// Files among `file_ids`, in no particular order. Unknown ids and files which are not stored are
+/// skipped.
+pub fn find_files(conn: &mut PgConnection, file_ids: Vec<uuid::Uuid>) -> QueryResult<Vec<StoredFile>> {
+    files::table
+        .filter(files::id.eq_any(file_ids))
+        .filter(files::status.eq(FileStatus::Stored.as_str()))
+        .select(StoredFile::as_select())
+        .load(conn)
+}
+
+/// Presigned upload `file_id` whose URL has not expired yet
+pub fn find_pending_file(conn: &mut PgConnection, file_id: uuid::Uuid) -> QueryResult<Option<StoredFile>> {
+    files::table
+        .find(file_id)
+        .filter(files::status.eq(FileStatus::Pending.as_str()))
+        .filter(files::upload_url_expires_at.gt(diesel::dsl::now))
+        .select(StoredFile::as_select())
+        .first(conn)
+        .optional()
+}
+
+/// Mark a pending upload as stored, unless its URL expired in the meantime
+pub fn confirm_file(conn: &mut PgConnection, file_id: uuid::Uuid) -> QueryResult<Option<StoredFile>> {
+    diesel::update(
+        files::table
+            .find(file_id)
+            .filter(files::status.eq(FileStatus::Pending.as_str()))
+            .filter(files::upload_url_expires_at.gt(diesel::dsl::now)),
+    )
+    .set(files::status.eq(FileStatus::Stored.as_str()))
+    .returning(StoredFile::as_returning())
+    .get_result(conn)
+    .optional()
+}
+
+/// Remove a file whose object was deleted.
+///
+/// While the presigned URL the file was uploaded through is valid, the object can be put again,
+/// so the record is kept as [`FileStatus::Deleted`] with its size still accounted; the sweeper
+/// releases it once the URL expired. Other files are released right away.
+pub fn delete_file(conn: &mut PgConnection, file_id: uuid::Uuid) -> QueryResult<Option<StoredFile>> {
+    conn.transaction(|conn| {
+        let tombstone = diesel::update(
+            files::table
+                .find(file_id)
+                .filter(files::upload_url_expires_at.gt(diesel::dsl::now)),
+        )
+        .set(files::status.eq(FileStatus::Deleted.as_str()))
+        .returning(StoredFile::as_returning())
+        .get_result(conn)
+        .optional()?;
+
+        match tombstone {
+            Some(file) => Ok(Some(file)),
+            None => release_file(conn, file_id),
+        }
+    })
+}
+
+/// Pending and deleted presigned uploads whose URL expired before `cutoff`, at most `limit` of them
+pub fn find_expired_files(
+    conn: &mut PgConnection,
+    cutoff: DateTime<Utc>,
+    limit: i64,
+) -> QueryResult<Vec<StoredFile>> {
+    files::table
+        .filter(files::status.ne(FileStatus::Stored.as_str()))
+        .filter(files::upload_url_expires_at.le(cutoff))
+        .select(StoredFile::as_select())
+        .order(files::upload_url_expires_at.asc())
+        .limit(limit)
+        .load(conn)
+}
+
+/// Usage of `owner_id`, zero when nothing was stored yet
+pub fn get_usage(conn: &mut PgConnection, owner_id: &str) -> QueryResult<StorageUsage> {
+    ensure_usage_row(conn, owner_id)?;
+    storage_usage::table
+        .find(owner_id)
+        .select(StorageUsage::as_select())
+        .first(conn)
+}
+
+/// Usage of every owner, largest consumers first
+pub fn list_usage(conn: &mut PgConnection, limit: i64, offset: i64) -> QueryResult<Vec<StorageUsage>> {
+    storage_usage::table
+        .select(StorageUsage::as_select())
+        .order((storage_usage::bytes_used.desc(), storage_usage::owner_id.asc()))
+        .limit(limit)
+        .offset(offset)
+        .load(conn)
+}
+
+/// Override the quota of `owner_id`. `None` falls back to the deployment defaults.
+pub fn set_quota(
+    conn: &mut PgConnection,
+    owner_id: &str,
+    quota_bytes: Option<i64>,
+    quota_objects: Option<i64>,
+) -> QueryResult<StorageUsage> {
+    ensure_usage_row(conn, owner_id)?;
+    diesel::update(storage_usage::table.find(owner_id))
+        .set((
+            storage_usage::quota_bytes.eq(quota_bytes),
+            storage_usage::quota_objects.eq(quota_objects),
+            storage_usage::updated_at.eq(diesel::dsl::now),
+        ))
+        .returning(StorageUsage::as_returning())
+        .get_result(conn)
+}
+
+fn ensure_usage_row(conn: &mut PgConnection, owner_id: &str) -> QueryResult<usize> {
+    diesel::insert_into(storage_usage::table)
+        .values(storage_usage::owner_id.eq(owner_id))
+        .on_conflict_do_nothing()
+        .execute(conn)
+}
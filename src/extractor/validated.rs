@@ -0,0 +1,267 @@
+//! Extractors deserializing the request and checking it against its declarative
+//! [`Validate`] rules.
+//!
+//! Bodies are decoded as JSON, MessagePack or CBOR according to their `Content-Type`; any other
+//! or a missing `Content-Type` is rejected with `415`, and a body over the limit with `413`.
+//! Malformed input and failed rules are reported as an [`ApiError`] too, so clients always
+//! receive the `GenericResponse` envelope rather than axum's plain text rejections.
+
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::rejection::{BytesRejection, JsonRejection, QueryRejection};
+use axum::extract::{FromRequest, FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::{Request, StatusCode};
+use axum::Json;
+use serde::de::DeserializeOwned;
+use serde_json::Map;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::constants::request_constants::MAX_BUFFERED_BODY_BYTES;
+use crate::response::api_error::{ApiError, FieldError};
+use crate::response::api_response::STATUS_MALFORMED_REQUEST;
+use crate::response::format::BodyFormat;
+
+/// JSON, MessagePack or CBOR body passing the validation rules of `T`. Named after the JSON
+/// bodies it first accepted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedJson<T>(pub T);
+
+/// Query string passing the validation rules of `T`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ValidatedQuery<T>(pub T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+    B: Send + 'static,
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    Bytes: FromRequest<S, B, Rejection = BytesRejection>,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let value = match BodyFormat::from_content_type(req.headers()) {
+            Some(format @ (BodyFormat::MessagePack | BodyFormat::Cbor)) => {
+                let body = Bytes::from_request(req, state).await.map_err(bytes_rejection)?;
+                format.decode::<T>(&body).map_err(|err| malformed(err.to_string()))?
+            }
+            _ => {
+                let Json(value) = Json::<T>::from_request(req, state).await.map_err(json_rejection)?;
+                value
+            }
+        };
+        value.validate()?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection: QueryRejection| malformed(rejection.body_text()))?;
+        value.validate()?;
+        Ok(ValidatedQuery(value))
+    }
+}
+
+fn json_rejection(rejection: JsonRejection) -> ApiError {
+    match rejection {
+        JsonRejection::MissingJsonContentType(_) => ApiError::UnsupportedMediaType(
+            "the body must be JSON, MessagePack or CBOR, as declared by its Content-Type".to_string(),
+        ),
+        JsonRejection::BytesRejection(rejection) => bytes_rejection(rejection),
+        rejection => malformed(rejection.body_text()),
+    }
+}
+
+fn bytes_rejection(rejection: BytesRejection) -> ApiError {
+    if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::PayloadTooLarge {
+            limit: MAX_BUFFERED_BODY_BYTES as u64,
+        }
+    } else {
+        malformed(rejection.body_text())
+    }
+}
+
+fn malformed(reason: String) -> ApiError {
+    ApiError::Validation {
+        status_code: STATUS_MALFORMED_REQUEST,
+        reason,
+    }
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = Vec::new();
+        collect_field_errors(&errors, "", &mut fields);
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+        ApiError::InvalidFields(fields)
+    }
+}
+
+/// Flatten nested struct and list errors into dotted paths such as `owners[2].name`
+fn collect_field_errors(errors: &ValidationErrors, prefix: &str, out: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                out.extend(errors.iter().map(|error| {
+                    // The rejected value is left out: it may be a secret.
+                    let params: Map<_, _> = error
+                        .params
+                        .iter()
+                        .filter(|(name, _)| *name != "value")
+                        .map(|(name, value)| (name.to_string(), value.clone()))
+                        .collect();
+                    FieldError {
+                        field: path.clone(),
+                        reason: error.code.to_string(),
+                        message: error.message.as_ref().map(|message| message.to_string()),
+                        params,
+                    }
+                }));
+            }
+            ValidationErrorsKind::Struct(errors) => collect_field_errors(errors, &path, out),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_errors(errors, &format!("{}[{}]", path, index), out);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    use crate::response::api_response::STATUS_UNSUPPORTED_MEDIA_TYPE;
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Owner {
+        #[validate(email)]
+        email: String,
+    }
+
+    #[derive(Debug, Deserialize, Validate)]
+    struct Request {
+        #[validate(length(min = 1, max = 8))]
+        name: String,
+        #[validate(range(min = 1, max = 10))]
+        count: u32,
+        #[validate]
+        owners: Vec<Owner>,
+    }
+
+    fn field_errors(request: Request) -> Vec<FieldError> {
+        match ApiError::from(request.validate().unwrap_err()) {
+            ApiError::InvalidFields(fields) => fields,
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_collect_field_errors() {
+        let fields = field_errors(Request {
+            name: "far too long".to_string(),
+            count: 11,
+            owners: vec![
+                Owner { email: "jane@example.com".to_string() },
+                Owner { email: "not an email".to_string() },
+            ],
+        });
+
+        let summary: Vec<(&str, &str)> = fields
+            .iter()
+            .map(|field| (field.field.as_str(), field.reason.as_str()))
+            .collect();
+        assert_eq!(summary, [("count", "range"), ("name", "length"), ("owners[1].email", "email")]);
+        assert_eq!(fields[0].params["max"], 10.0);
+        assert!(!fields[1].params.contains_key("value"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json() {
+        let req = axum::http::Request::builder()
+            .header(axum::http::header::CONTENT_TYPE, "application/json")
+            .body(axum::body::Body::from(r#"{"name": "report", "count": "two", "owners": []}"#))
+            .unwrap();
+
+        match ValidatedJson::<Request>::from_request(req, &()).await {
+            Err(ApiError::Validation { status_code, reason }) => {
+                assert_eq!(status_code, STATUS_MALFORMED_REQUEST);
+                assert!(reason.contains("count"), "{}", reason);
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_content_type() {
+        for content_type in [None, Some("text/plain")] {
+            let mut req = axum::http::Request::builder();
+            if let Some(content_type) = content_type {
+                req = req.header(axum::http::header::CONTENT_TYPE, content_type);
+            }
+            let req = req.body(axum::body::Body::from("name=report")).unwrap();
+
+            let err = ValidatedJson::<Request>::from_request(req, &()).await.err().unwrap();
+            assert_eq!(err.status(), (StatusCode::UNSUPPORTED_MEDIA_TYPE, STATUS_UNSUPPORTED_MEDIA_TYPE));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_body_over_the_limit() {
+        for content_type in ["application/json", "application/cbor"] {
+            let req = axum::http::Request::builder()
+                .header(axum::http::header::CONTENT_TYPE, content_type)
+                .body(axum::body::Body::from(vec![b' '; MAX_BUFFERED_BODY_BYTES + 1]))
+                .unwrap();
+
+            match ValidatedJson::<Request>::from_request(req, &()).await {
+                Err(ApiError::PayloadTooLarge { limit }) => assert_eq!(limit, MAX_BUFFERED_BODY_BYTES as u64),
+                other => panic!("unexpected result {:?}", other.map(|_| ())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_body() {
+        #[derive(serde::Serialize)]
+        struct Body<'a> {
+            name: &'a str,
+            count: u32,
+            owners: Vec<()>,
+        }
+        let body = BodyFormat::MessagePack
+            .encode(&Body { name: "report", count: 20, owners: vec![] })
+            .unwrap();
+        let req = axum::http::Request::builder()
+            .header(axum::http::header::CONTENT_TYPE, "application/msgpack")
+            .body(axum::body::Body::from(body))
+            .unwrap();
+
+        match ValidatedJson::<Request>::from_request(req, &()).await {
+            Err(ApiError::InvalidFields(fields)) => assert_eq!(fields[0].field, "count"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -1 +1,8 @@
-pub mod version_handler;
\ No newline at end of file
+pub mod version_handler;
+pub mod file_handler;
+pub mod usage_handler;
+pub mod archive_handler;
+pub mod meta_handler;
+pub mod log_level_handler;
+pub mod metrics_handler;
+pub mod health_handler;
\ No newline at end of file
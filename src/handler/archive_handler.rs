@@ -0,0 +1,122 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    body::StreamBody,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::archive::{stream_archive, unique_entry_names, ArchiveEntry};
+use crate::authentication::jwt::Claims;
+use crate::constants::archive_constants::DEFAULT_ARCHIVE_NAME;
+use crate::database::usage_repository;
+use crate::database::with_connection;
+use crate::extractor::validated::ValidatedJson;
+use crate::handler::file_handler::may_access;
+use crate::response::api_error::ApiError;
+use crate::state::AppState;
+use crate::util::zip_stream::framing_size;
+
+/// Body of [`create_archive`]
+#[derive(Debug, Deserialize, Validate)]
+pub struct ArchiveRequest {
+    #[validate(length(min = 1))]
+    pub file_ids: Vec<uuid::Uuid>,
+    /// File name of the archive, without the `.zip` extension
+    #[validate(length(max = 255))]
+    pub name: Option<String>,
+}
+
+/// `POST /files/archive`: download a selection of files as a ZIP archive.
+///
+/// Every file must belong to the caller, unless they are an admin, and the archive must stay
+/// below the configured maximum size. The archive is streamed from the storage backend as it
+/// is built.
+pub async fn create_archive(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ValidatedJson(request): ValidatedJson<ArchiveRequest>,
+) -> Result<Response, ApiError> {
+    let policy = &state.archive_policy;
+
+    let mut seen = HashSet::new();
+    let file_ids: Vec<uuid::Uuid> = request.file_ids.into_iter().filter(|id| seen.insert(*id)).collect();
+    if file_ids.is_empty() || file_ids.len() > policy.max_files {
+        let reason = format!("between 1 and {} files can be archived", policy.max_files);
+        return Err(ApiError::validation(reason));
+    }
+
+    let ids = file_ids.clone();
+    let files = with_connection(&state.db_pool, move |conn| usage_repository::find_files(conn, ids))
+        .await?;
+
+    let mut files_by_id: HashMap<uuid::Uuid, _> = files.into_iter().map(|file| (file.id, file)).collect();
+    let missing: Vec<String> = file_ids
+        .iter()
+        .filter(|id| !files_by_id.contains_key(id))
+        .map(|id| id.to_string())
+        .collect();
+    if !missing.is_empty() {
+        return Err(ApiError::NotFound(format!("files not found: {}", missing.join(", "))));
+    }
+
+    let forbidden: Vec<String> = files_by_id
+        .values()
+        .filter(|file| !may_access(&claims, file))
+        .map(|file| file.id.to_string())
+        .collect();
+    if !forbidden.is_empty() {
+        return Err(ApiError::forbidden(format!("files not owned by the caller: {}", forbidden.join(", "))));
+    }
+
+    let files: Vec<_> = file_ids.iter().filter_map(|id| files_by_id.remove(id)).collect();
+    let names = unique_entry_names(
+        files
+            .iter()
+            .map(|file| file.file_name.clone().unwrap_or(file.id.to_string())),
+    );
+
+    let content_size: u64 = files.iter().map(|file| file.size_bytes.max(0) as u64).sum();
+    let archive_size = content_size + framing_size(names.iter().map(String::len));
+    if archive_size > policy.max_total_bytes {
+        return Err(ApiError::PayloadTooLarge { limit: policy.max_total_bytes });
+    }
+
+    let entries = files
+        .into_iter()
+        .zip(names)
+        .map(|(file, name)| ArchiveEntry {
+            key: file.id.to_string(),
+            name,
+            modified: file.created_at,
+        })
+        .collect();
+
+    let archive_name = archive_file_name(request.name.as_deref());
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/zip"));
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}.zip\"", archive_name)).unwrap(),
+    );
+
+    let body = StreamBody::new(stream_archive(state.s3_client.clone(), entries));
+    Ok((StatusCode::OK, headers, body).into_response())
+}
+
+/// Keep only the characters which are safe in a `Content-Disposition` file name
+fn archive_file_name(name: Option<&str>) -> String {
+    let name: String = name
+        .unwrap_or(DEFAULT_ARCHIVE_NAME)
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+        .collect();
+    match name.trim().trim_matches('.') {
+        "" => DEFAULT_ARCHIVE_NAME.to_string(),
+        name => name.to_string(),
+    }
+}
@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+use std::io;
+
+use aws_sdk_s3::primitives::{ByteStream, DateTimeFormat};
+use axum::{
+    body::{Bytes, StreamBody},
+    extract::{BodyStream, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::Utc;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use log::error;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use tokio_util::io::ReaderStream;
+
+use crate::authentication::jwt::Claims;
+use crate::authentication::role::Role;
+use crate::constants::upload_constants::{FILE_NAME_HEADER, MAX_FILE_NAME_LEN, SNIFF_PREFIX_LEN};
+use crate::database::models::{FileStatus, NewStoredFile, StoredFile};
+use crate::database::usage_repository::{self, ReserveOutcome};
+use crate::database::with_connection;
+use crate::extractor::validated::ValidatedJson;
+use crate::response::api_error::ApiError;
+use crate::response::api_response::*;
+use crate::s3_client::client::S3Client;
+use crate::state::AppState;
+use crate::upload::integrity::ExpectedChecksums;
+use crate::upload::sniff::sniff_content_type;
+use crate::upload::spool::spool_body;
+use crate::upload::UploadError;
+use crate::util::http_conditional::{if_none_match_matches, if_range_matches, not_modified_since};
+use crate::util::http_range::{parse_range_header, ByteRange, RangeError};
+use crate::variants::VariantGenerator;
+
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Download a stored file. Only its owner or an admin may download it.
+///
+/// Supports single and multi-range requests (`206`/`416`) and the `If-None-Match`,
+/// `If-Modified-Since` and `If-Range` preconditions evaluated against the object ETag and
+/// last-modified date. Ranges are passed through to the object store and the bodies are
+/// streamed to the client without buffering.
+pub async fn download_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(file_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let file = find_accessible_file(&state, &claims, &file_id, "only the owner may download the file").await?;
+    serve_object(&state, file.id.to_string(), &headers).await
+}
+
+/// Download a derivative generated for an uploaded image, with the same range and
+/// conditional request support as [`download_file`].
+///
+/// Derivatives are generated asynchronously, so this returns `404` until they are stored. Only
+/// the owner of the file or an admin may download them.
+pub async fn download_variant(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((file_id, name)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    if state.variant_generator.spec(&name).is_none() {
+        return Err(ApiError::not_found());
+    }
+    let file = find_accessible_file(&state, &claims, &file_id, "only the owner may download the file").await?;
+    serve_object(&state, VariantGenerator::variant_key(&file.id.to_string(), &name), &headers).await
+}
+
+/// Stored file `file_id`, which only its owner or an admin may access; `forbidden` is the reason
+/// given to the other callers
+async fn find_accessible_file(
+    state: &AppState,
+    claims: &Claims,
+    file_id: &str,
+    forbidden: &str,
+) -> Result<StoredFile, ApiError> {
+    let file_id = uuid::Uuid::parse_str(file_id).map_err(|_| ApiError::not_found())?;
+    let file = with_connection(&state.db_pool, move |conn| usage_repository::find_file(conn, file_id))
+        .await?
+        .ok_or_else(ApiError::not_found)?;
+    if !may_access(claims, &file) {
+        return Err(ApiError::forbidden(forbidden));
+    }
+    Ok(file)
+}
+
+/// Whether the caller owns `file` or is an admin
+pub fn may_access(claims: &Claims, file: &StoredFile) -> bool {
+    file.owner_id == claims.sub || Role::from_str(&claims.role) == Role::Admin
+}
+
+async fn serve_object(state: &AppState, key: String, headers: &HeaderMap) -> Result<Response, ApiError> {
+    let head = state.s3_client.head_object(&key).await?;
+
+    let total_len = head.content_length().unwrap_or(0).max(0) as u64;
+    let etag = head.e_tag().map(str::to_owned);
+    let last_modified = head.last_modified().cloned();
+    let content_type = head.content_type().unwrap_or(DEFAULT_CONTENT_TYPE).to_owned();
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(value) = etag.as_deref().and_then(|etag| HeaderValue::from_str(etag).ok()) {
+        response_headers.insert(header::ETAG, value);
+    }
+    if let Some(value) = last_modified
+        .and_then(|date| date.fmt(DateTimeFormat::HttpDate).ok())
+        .and_then(|date| HeaderValue::from_str(&date).ok())
+    {
+        response_headers.insert(header::LAST_MODIFIED, value);
+    }
+
+    let not_modified = match header_str(headers, header::IF_NONE_MATCH) {
+        Some(if_none_match) => etag
+            .as_deref()
+            .is_some_and(|etag| if_none_match_matches(if_none_match, etag)),
+        None => match (header_str(headers, header::IF_MODIFIED_SINCE), &last_modified) {
+            (Some(since), Some(last_modified)) => not_modified_since(since, last_modified),
+            _ => false,
+        },
+    };
+    if not_modified {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    let range_applies = header_str(headers, header::IF_RANGE).is_none_or(|if_range| {
+        if_range_matches(if_range, etag.as_deref(), last_modified.as_ref())
+    });
+    let ranges = match header_str(headers, header::RANGE) {
+        Some(range) if range_applies => parse_range_header(range, total_len),
+        _ => Err(RangeError::Ignored),
+    };
+
+    match ranges {
+        Err(RangeError::Unsatisfiable) => Err(ApiError::RangeNotSatisfiable { total_len }),
+        Err(RangeError::Ignored) => {
+            let output = state
+                .s3_client
+                .get_object(&key, None, etag)
+                .await?;
+
+            response_headers.insert(header::CONTENT_TYPE, header_value(&content_type));
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(total_len));
+            Ok((StatusCode::OK, response_headers, stream_body(output.body)).into_response())
+        }
+        Ok(ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+            let output = state
+                .s3_client
+                .get_object(&key, Some(range.to_range_header()), etag)
+                .await?;
+
+            response_headers.insert(header::CONTENT_TYPE, header_value(&content_type));
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(range.len()));
+            response_headers.insert(
+                header::CONTENT_RANGE,
+                header_value(&range.to_content_range(total_len)),
+            );
+            Ok((StatusCode::PARTIAL_CONTENT, response_headers, stream_body(output.body)).into_response())
+        }
+        Ok(ranges) => {
+            let boundary = uuid::Uuid::new_v4().simple().to_string();
+            let body = multipart_byteranges(
+                state.s3_client.clone(),
+                key,
+                etag,
+                &content_type,
+                total_len,
+                ranges,
+                &boundary,
+            );
+
+            response_headers.insert(
+                header::CONTENT_TYPE,
+                header_value(&format!("multipart/byteranges; boundary={}", boundary)),
+            );
+            response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.content_length));
+            Ok((StatusCode::PARTIAL_CONTENT, response_headers, StreamBody::new(body.stream)).into_response())
+        }
+    }
+}
+
+/// Upload a new file from the raw request body.
+///
+/// The body is spooled to disk while its size is checked against the role limit and its
+/// `Content-MD5` / `X-Content-SHA256` checksums are computed. The content type is sniffed from
+/// the magic bytes and checked against the allowlist, and the size is reserved against the
+/// owner's storage quota. The object is only committed to the storage backend once every check
+/// passed. Derivatives of images are then generated in the background.
+pub async fn upload_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    body: BodyStream,
+) -> Result<(StatusCode, GenericResponse<UploadedFile>), ApiError> {
+    let policy = &state.upload_policy;
+    let max_size = policy.max_size_for(&Role::from_str(&claims.role));
+
+    let file_name = header_str(&headers, FILE_NAME_HEADER).map(str::to_string);
+    if file_name.as_ref().is_some_and(|name| name.chars().count() > MAX_FILE_NAME_LEN) {
+        return Err(UploadError::FileNameTooLong { limit: MAX_FILE_NAME_LEN }.into());
+    }
+
+    let declared_len = header_str(&headers, header::CONTENT_LENGTH).and_then(|len| len.parse::<u64>().ok());
+    if declared_len.is_some_and(|len| len > max_size) {
+        return Err(UploadError::TooLarge { limit: max_size }.into());
+    }
+    if let Some(len) = declared_len {
+        check_quota(&state, &claims.sub, len).await?;
+    }
+    let expected = ExpectedChecksums::from_headers(&headers)?;
+
+    let spooled = spool_body(body, &policy.tmp_dir, max_size).await?;
+    expected.verify(&spooled.digests)?;
+
+    let content_type = sniff_content_type(&spooled.prefix, header_str(&headers, header::CONTENT_TYPE));
+    if !policy.is_allowed(&content_type) {
+        return Err(UploadError::ContentTypeNotAllowed(content_type).into());
+    }
+
+    let file = reserve(&state, NewStoredFile {
+        id: uuid::Uuid::new_v4(),
+        owner_id: claims.sub.clone(),
+        size_bytes: spooled.size as i64,
+        content_type: content_type.clone(),
+        file_name: file_name.clone(),
+        status: FileStatus::Stored.as_str().to_string(),
+        upload_url_expires_at: None,
+    })
+    .await?;
+
+    let file_id = file.id.to_string();
+    let sha256 = spooled.digests.sha256_hex();
+    let mut metadata = HashMap::from([
+        ("owner".to_string(), claims.sub.clone()),
+        ("sha256".to_string(), sha256.clone()),
+    ]);
+    if let Some(file_name) = file_name {
+        metadata.insert("file-name".to_string(), file_name);
+    }
+
+    let stored = state
+        .s3_client
+        .put_object_from_path(
+            &file_id,
+            &spooled.path,
+            &content_type,
+            &spooled.digests.md5_base64(),
+            metadata,
+        )
+        .await;
+    if let Err(err) = stored {
+        release(&state, file.id).await;
+        return Err(ApiError::Upstream(err));
+    }
+
+    let variants = if state.variant_generator.applies_to(&content_type, spooled.size) {
+        state.variant_generator.spawn(file_id.clone());
+        state.variant_generator.specs().iter().map(|spec| spec.name.clone()).collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        GenericResponse::ok(UploadedFile {
+            file_id,
+            size: spooled.size,
+            content_type,
+            sha256,
+            variants,
+        }),
+    ))
+}
+
+/// Data of the [`upload_file`] response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadedFile {
+    pub file_id: String,
+    pub size: u64,
+    pub content_type: String,
+    pub sha256: String,
+    /// Names of the derivatives being generated in the background
+    pub variants: Vec<String>,
+}
+
+/// Body of [`create_presigned_upload`]
+#[derive(Debug, Deserialize, Validate)]
+pub struct PresignedUploadRequest {
+    #[validate(range(min = 1))]
+    pub size: u64,
+    #[validate(length(min = 1, max = 255))]
+    pub content_type: String,
+    #[validate(length(min = 1, max = 255))]
+    pub file_name: Option<String>,
+}
+
+/// Data of the [`create_presigned_upload`] response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresignedUpload {
+    pub file_id: String,
+    pub method: String,
+    pub url: String,
+    /// Headers the upload request must carry, as they were signed
+    pub headers: HashMap<String, String>,
+    pub expires_in: u64,
+}
+
+/// Data of the [`complete_presigned_upload`] response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletedUpload {
+    pub file_id: String,
+    pub size: u64,
+    pub content_type: String,
+    /// Names of the derivatives being generated in the background
+    pub variants: Vec<String>,
+}
+
+/// Data of the [`delete_file`] response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedFile {
+    pub file_id: String,
+}
+
+/// Reserve quota for a file and return a presigned URL the client uploads it to directly.
+///
+/// The URL is signed for the declared size and content type, so the object store rejects any
+/// other upload. Content sniffing and checksums are not applied on this path. The file stays
+/// pending until the upload is confirmed with [`complete_presigned_upload`]; the reservation is
+/// released after the URL expired otherwise.
+pub async fn create_presigned_upload(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ValidatedJson(request): ValidatedJson<PresignedUploadRequest>,
+) -> Result<(StatusCode, GenericResponse<PresignedUpload>), ApiError> {
+    let policy = &state.upload_policy;
+    let max_size = policy.max_size_for(&Role::from_str(&claims.role));
+    if request.size > max_size {
+        return Err(UploadError::TooLarge { limit: max_size }.into());
+    }
+    let content_type = request.content_type.trim().to_ascii_lowercase();
+    if !policy.is_allowed(&content_type) {
+        return Err(UploadError::ContentTypeNotAllowed(content_type).into());
+    }
+
+    let expires_in = state.upload_policy.presigned_expiry;
+    let file = reserve(&state, NewStoredFile {
+        id: uuid::Uuid::new_v4(),
+        owner_id: claims.sub.clone(),
+        size_bytes: request.size as i64,
+        content_type: content_type.clone(),
+        file_name: request.file_name.clone(),
+        status: FileStatus::Pending.as_str().to_string(),
+        upload_url_expires_at: Some(Utc::now() + chrono::Duration::seconds(expires_in.as_secs() as i64)),
+    })
+    .await?;
+
+    let mut metadata = HashMap::from([("owner".to_string(), claims.sub.clone())]);
+    if let Some(file_name) = request.file_name {
+        metadata.insert("file-name".to_string(), file_name);
+    }
+
+    let presigned = state
+        .s3_client
+        .presign_put_object(&file.id.to_string(), &content_type, file.size_bytes, metadata, expires_in)
+        .await;
+    let presigned = match presigned {
+        Ok(presigned) => presigned,
+        Err(err) => {
+            release(&state, file.id).await;
+            return Err(ApiError::Upstream(err));
+        }
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        GenericResponse::ok(PresignedUpload {
+            file_id: file.id.to_string(),
+            method: presigned.method().to_string(),
+            url: presigned.uri().to_string(),
+            headers: presigned
+                .headers()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            expires_in: expires_in.as_secs(),
+        }),
+    ))
+}
+
+/// Confirm a presigned upload once the client has uploaded the object.
+///
+/// The object must be in the storage backend with the reserved size, and its leading bytes must
+/// sniff as the declared content type, which the allowlist still accepts. An object failing the
+/// content checks is deleted along with the file. A confirmed file can be downloaded like any
+/// other. Only the owner or an admin may confirm the upload, and only before the URL expires.
+pub async fn complete_presigned_upload(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(file_id): Path<String>,
+) -> Result<GenericResponse<CompletedUpload>, ApiError> {
+    let file_id = uuid::Uuid::parse_str(&file_id).map_err(|_| ApiError::not_found())?;
+    let file = with_connection(&state.db_pool, move |conn| usage_repository::find_pending_file(conn, file_id))
+        .await?
+        .ok_or_else(ApiError::not_found)?;
+    if !may_access(&claims, &file) {
+        return Err(ApiError::forbidden("only the owner may complete the upload"));
+    }
+
+    let head = match state.s3_client.head_object(&file.id.to_string()).await {
+        Ok(head) => head,
+        Err(err) => {
+            return Err(match ApiError::from(err) {
+                ApiError::NotFound(_) => ApiError::Conflict("the file has not been uploaded yet".to_string()),
+                err => err,
+            })
+        }
+    };
+    if head.content_length() != Some(file.size_bytes) {
+        return Err(ApiError::Conflict(format!(
+            "the uploaded object does not have the reserved size of {} bytes",
+            file.size_bytes
+        )));
+    }
+
+    let detected = sniff_stored_object(&state, &file).await?;
+    let rejected = if !state.upload_policy.is_allowed(&detected) {
+        Some(UploadError::ContentTypeNotAllowed(detected))
+    } else if detected != file.content_type {
+        Some(UploadError::ContentTypeMismatch {
+            declared: file.content_type.clone(),
+            detected,
+        })
+    } else {
+        None
+    };
+    if let Some(rejected) = rejected {
+        state
+            .s3_client
+            .delete_object(&file.id.to_string())
+            .await
+            .map_err(ApiError::Upstream)?;
+        with_connection(&state.db_pool, move |conn| usage_repository::delete_file(conn, file_id)).await?;
+        return Err(rejected.into());
+    }
+
+    let file = with_connection(&state.db_pool, move |conn| usage_repository::confirm_file(conn, file_id))
+        .await?
+        .ok_or_else(ApiError::not_found)?;
+
+    let size = file.size_bytes as u64;
+    let variants = if state.variant_generator.applies_to(&file.content_type, size) {
+        state.variant_generator.spawn(file.id.to_string());
+        state.variant_generator.specs().iter().map(|spec| spec.name.clone()).collect()
+    } else {
+        Vec::new()
+    };
+
+    Ok(GenericResponse::ok(CompletedUpload {
+        file_id: file.id.to_string(),
+        size,
+        content_type: file.content_type,
+        variants,
+    }))
+}
+
+/// Delete a stored file and give its size back to the owner's quota.
+///
+/// Only the owner or an admin may delete a file. The size of a file uploaded through a presigned
+/// URL is only given back once the URL expired, as the object could be put again until then.
+pub async fn delete_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(file_id): Path<String>,
+) -> Result<GenericResponse<DeletedFile>, ApiError> {
+    let file = find_accessible_file(&state, &claims, &file_id, "only the owner may delete the file").await?;
+    let file_id = file.id;
+
+    state
+        .s3_client
+        .delete_object(&file.id.to_string())
+        .await
+        .map_err(ApiError::Upstream)?;
+    state.variant_generator.delete_variants(&file.id.to_string()).await;
+    with_connection(&state.db_pool, move |conn| usage_repository::delete_file(conn, file_id)).await?;
+
+    Ok(GenericResponse::ok(DeletedFile { file_id: file.id.to_string() }))
+}
+
+/// Content type of the object of `file`, sniffed from its leading bytes like a direct upload
+async fn sniff_stored_object(state: &AppState, file: &StoredFile) -> Result<String, ApiError> {
+    let range = format!("bytes=0-{}", SNIFF_PREFIX_LEN - 1);
+    let output = state.s3_client.get_object(&file.id.to_string(), Some(range), None).await?;
+    let prefix = output
+        .body
+        .collect()
+        .await
+        .map_err(|err| ApiError::Upstream(err.into()))?
+        .into_bytes();
+    Ok(sniff_content_type(&prefix, Some(&file.content_type)))
+}
+
+/// Reject the upload early when `size` more bytes would not fit in the owner's quota
+async fn check_quota(state: &AppState, owner_id: &str, size: u64) -> Result<(), UploadError> {
+    let owner_id = owner_id.to_string();
+    let usage = with_connection(&state.db_pool, move |conn| usage_repository::get_usage(conn, &owner_id)).await?;
+    if state.quota_policy.would_exceed(&usage, size as i64) {
+        return Err(UploadError::QuotaExceeded);
+    }
+    Ok(())
+}
+
+async fn reserve(state: &AppState, new_file: NewStoredFile) -> Result<StoredFile, UploadError> {
+    let policy = state.quota_policy.clone();
+    let outcome = with_connection(&state.db_pool, move |conn| {
+        usage_repository::reserve_file(conn, new_file, &policy)
+    })
+    .await?;
+
+    match outcome {
+        ReserveOutcome::Reserved(file) => Ok(file),
+        ReserveOutcome::QuotaExceeded => Err(UploadError::QuotaExceeded),
+    }
+}
+
+/// Give back a reservation whose object could not be committed
+async fn release(state: &AppState, file_id: uuid::Uuid) {
+    let released = with_connection(&state.db_pool, move |conn| usage_repository::release_file(conn, file_id)).await;
+    if let Err(err) = released {
+        error!("failed to release storage reservation of file {}: {}", file_id, err);
+    }
+}
+
+/// A `multipart/byteranges` body whose parts are fetched from the object store one at a time
+struct MultipartBody {
+    stream: BoxStream<'static, io::Result<Bytes>>,
+    content_length: u64,
+}
+
+fn multipart_byteranges(
+    client: S3Client,
+    key: String,
+    etag: Option<String>,
+    content_type: &str,
+    total_len: u64,
+    ranges: Vec<ByteRange>,
+    boundary: &str,
+) -> MultipartBody {
+    let closing = Bytes::from(format!("\r\n--{}--\r\n", boundary));
+    let mut content_length = closing.len() as u64;
+
+    let mut parts = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let part_header = Bytes::from(format!(
+            "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+            boundary,
+            content_type,
+            range.to_content_range(total_len),
+        ));
+        content_length += part_header.len() as u64 + range.len();
+
+        let client = client.clone();
+        let key = key.clone();
+        let etag = etag.clone();
+        let part_body = stream::once(async move {
+            client.get_object(&key, Some(range.to_range_header()), etag).await
+        })
+        .flat_map(|output| match output {
+            Ok(output) => reader_stream(output.body),
+            Err(err) => {
+                error!("failed to fetch range of stored file: {}", err);
+                stream::once(async move { Err(io::Error::other(err)) }).boxed()
+            }
+        });
+
+        parts.push(stream::once(async move { Ok(part_header) }).chain(part_body));
+    }
+
+    MultipartBody {
+        stream: stream::iter(parts)
+            .flatten()
+            .chain(stream::once(async move { Ok(closing) }))
+            .boxed(),
+        content_length,
+    }
+}
+
+fn stream_body(body: ByteStream) -> StreamBody<BoxStream<'static, io::Result<Bytes>>> {
+    StreamBody::new(reader_stream(body))
+}
+
+fn reader_stream(body: ByteStream) -> BoxStream<'static, io::Result<Bytes>> {
+    ReaderStream::new(body.into_async_read()).boxed()
+}
+
+fn header_str(headers: &HeaderMap, name: impl header::AsHeaderName) -> Option<&str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or(HeaderValue::from_static(DEFAULT_CONTENT_TYPE))
+}
@@ -0,0 +1,28 @@
+use axum::{extract::State, http::StatusCode};
+
+use crate::health::{readiness, HealthStatus, Readiness};
+use crate::i18n::status_message;
+use crate::response::api_response::*;
+use crate::state::AppState;
+
+/// `GET /health/live`: the process is up and serving requests
+pub async fn get_live() -> GenericResponse<Empty> {
+    GenericResponse::ok(Empty::default())
+}
+
+/// `GET /health/ready`: status and latency of every dependency, `503` when a critical one is
+/// down
+pub async fn get_ready(State(state): State<AppState>) -> (StatusCode, GenericResponse<Readiness>) {
+    let readiness = readiness(&state).await;
+    match readiness.status {
+        HealthStatus::Up => (StatusCode::OK, GenericResponse::ok(readiness)),
+        HealthStatus::Down => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            GenericResponse::new(
+                STATUS_SERVICE_UNAVAILABLE,
+                status_message(STATUS_SERVICE_UNAVAILABLE, &[]),
+                readiness,
+            ),
+        ),
+    }
+}
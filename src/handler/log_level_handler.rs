@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use axum::Extension;
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::authentication::jwt::Claims;
+use crate::constants::logging_constants::MAX_LOG_LEVEL_REVERT_SECS;
+use crate::extractor::validated::ValidatedJson;
+use crate::logging::log_level::{self, Directives, LogLevel};
+use crate::response::api_error::ApiError;
+use crate::response::api_response::*;
+
+/// Body of [`set_log_level`]
+#[derive(Debug, Deserialize, Validate)]
+pub struct LogLevelRequest {
+    /// Directives in the `RUST_LOG` syntax, such as `info,axum_api::upload=debug`
+    #[validate(length(min = 1, max = 1024))]
+    pub directives: String,
+    /// Seconds after which the previous directives are restored, permanent when omitted
+    #[validate(range(min = 1, max = "MAX_LOG_LEVEL_REVERT_SECS"))]
+    pub revert_after_secs: Option<u64>,
+}
+
+/// `GET /admin/log-level`: level directives in effect
+pub async fn get_log_level() -> GenericResponse<LogLevel> {
+    GenericResponse::ok(log_level::current())
+}
+
+/// `PUT /admin/log-level`: replace the level directives, optionally for a limited time
+pub async fn set_log_level(
+    Extension(claims): Extension<Claims>,
+    ValidatedJson(request): ValidatedJson<LogLevelRequest>,
+) -> Result<GenericResponse<LogLevel>, ApiError> {
+    let directives: Directives = request
+        .directives
+        .parse()
+        .map_err(|err: log_level::DirectiveError| ApiError::validation(err.to_string()))?;
+
+    log_level::set(directives, request.revert_after_secs.map(Duration::from_secs), &claims.sub);
+    Ok(GenericResponse::ok(log_level::current()))
+}
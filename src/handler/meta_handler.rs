@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+use crate::response::api_response::*;
+
+/// A domain of the status code catalog and the range its codes are allocated in
+#[derive(Debug, Clone, Serialize)]
+pub struct DomainInfo {
+    pub domain: StatusDomain,
+    pub min_code: u16,
+    pub max_code: u16,
+}
+
+/// Data of the [`list_status_codes`] response
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusCatalog {
+    pub domains: Vec<DomainInfo>,
+    pub codes: &'static [StatusCodeInfo],
+}
+
+/// `GET /meta/status-codes`: every application status code clients may receive
+pub async fn list_status_codes() -> GenericResponse<StatusCatalog> {
+    let domains = StatusDomain::ALL
+        .iter()
+        .map(|&domain| {
+            let (min_code, max_code) = domain.range();
+            DomainInfo { domain, min_code, max_code }
+        })
+        .collect();
+
+    GenericResponse::ok(StatusCatalog {
+        domains,
+        codes: STATUS_CODES,
+    })
+}
@@ -0,0 +1,18 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+};
+
+use crate::metrics::metrics;
+use crate::state::AppState;
+
+/// Expose the metrics in the Prometheus text format, sampling the database pool first
+pub async fn get_metrics(State(state): State<AppState>) -> Response {
+    metrics().observe_db_pool(&state.db_pool);
+    (
+        [(header::CONTENT_TYPE, HeaderValue::from_static(prometheus::TEXT_FORMAT))],
+        metrics().encode(),
+    )
+        .into_response()
+}
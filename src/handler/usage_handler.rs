@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Path, State},
+    Extension,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::authentication::jwt::Claims;
+use crate::database::models::StorageUsage;
+use crate::database::usage_repository;
+use crate::database::with_connection;
+use crate::extractor::validated::{ValidatedJson, ValidatedQuery};
+use crate::response::api_error::ApiError;
+use crate::response::api_response::*;
+use crate::state::AppState;
+use crate::upload::quota::QuotaPolicy;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 500;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UsageListQuery {
+    #[validate(range(min = 1, max = "MAX_PAGE_SIZE"))]
+    pub limit: Option<i64>,
+    #[validate(range(min = 0))]
+    pub offset: Option<i64>,
+}
+
+/// Body of [`set_owner_quota`]. Omitted limits fall back to the deployment defaults.
+#[derive(Debug, Deserialize, Validate)]
+pub struct QuotaRequest {
+    #[validate(range(min = 0))]
+    pub quota_bytes: Option<i64>,
+    #[validate(range(min = 0))]
+    pub quota_objects: Option<i64>,
+}
+
+/// Storage used by an owner and the quota applying to them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub owner_id: String,
+    pub bytes_used: i64,
+    pub object_count: i64,
+    /// `None` when the owner is not limited
+    pub quota_bytes: Option<i64>,
+    pub quota_objects: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UsageReport {
+    fn new(usage: &StorageUsage, policy: &QuotaPolicy) -> Self {
+        let (quota_bytes, quota_objects) = policy.limits_for(usage);
+        UsageReport {
+            owner_id: usage.owner_id.clone(),
+            bytes_used: usage.bytes_used,
+            object_count: usage.object_count,
+            quota_bytes,
+            quota_objects,
+            updated_at: usage.updated_at,
+        }
+    }
+}
+
+/// Data of the [`list_usage`] response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsagePage {
+    pub owners: Vec<UsageReport>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// `GET /me/usage`: storage used by the caller and the quota applying to them
+pub async fn get_my_usage(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<GenericResponse<UsageReport>, ApiError> {
+    let usage = with_connection(&state.db_pool, move |conn| usage_repository::get_usage(conn, &claims.sub))
+        .await?;
+
+    Ok(GenericResponse::ok(UsageReport::new(&usage, &state.quota_policy)))
+}
+
+/// `GET /admin/usage`: usage report of every owner, largest consumers first
+pub async fn list_usage(
+    State(state): State<AppState>,
+    ValidatedQuery(query): ValidatedQuery<UsageListQuery>,
+) -> Result<GenericResponse<UsagePage>, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+
+    let usages = with_connection(&state.db_pool, move |conn| usage_repository::list_usage(conn, limit, offset))
+        .await?;
+
+    let owners = usages
+        .iter()
+        .map(|usage| UsageReport::new(usage, &state.quota_policy))
+        .collect();
+    Ok(GenericResponse::ok(UsagePage { owners, limit, offset }))
+}
+
+/// `GET /admin/usage/:owner_id`: usage of a single owner
+pub async fn get_owner_usage(
+    State(state): State<AppState>,
+    Path(owner_id): Path<String>,
+) -> Result<GenericResponse<UsageReport>, ApiError> {
+    let usage = with_connection(&state.db_pool, move |conn| usage_repository::get_usage(conn, &owner_id))
+        .await?;
+
+    Ok(GenericResponse::ok(UsageReport::new(&usage, &state.quota_policy)))
+}
+
+/// `PUT /admin/usage/:owner_id/quota`: override the quota of an owner
+pub async fn set_owner_quota(
+    State(state): State<AppState>,
+    Path(owner_id): Path<String>,
+    ValidatedJson(request): ValidatedJson<QuotaRequest>,
+) -> Result<GenericResponse<UsageReport>, ApiError> {
+    let usage = with_connection(&state.db_pool, move |conn| {
+        usage_repository::set_quota(conn, &owner_id, request.quota_bytes, request.quota_objects)
+    })
+    .await?;
+
+    Ok(GenericResponse::ok(UsageReport::new(&usage, &state.quota_policy)))
+}
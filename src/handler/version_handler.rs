@@ -1,24 +1,13 @@
-use std::collections::HashMap;
-use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
-};
-use log::info;
-use serde_json::{Value, Map, Number};
+use axum::{extract::State, http::StatusCode};
 
+use crate::build_info::{BuildInfo, VERSION};
 use crate::response::api_response::*;
+use crate::state::AppState;
 
-const API_VERSION: &str = "1.0.0";
+/// `GET /`: version of the API with its build and runtime information. The migration version
+/// is the one read at startup, and is left out when the database was unavailable then.
+pub async fn get_version(State(state): State<AppState>) -> (StatusCode, GenericResponse<BuildInfo>) {
+    let json_response = GenericResponse::new(STATUS_NO_ERROR, VERSION, BuildInfo::new(state.migration_version));
 
-pub async fn get_version() -> (StatusCode, Json<GenericResponse<'static>>)  {
-    let json_response = GenericResponse {
-        status: STATUS_MAPPER.get(&STATUS_NO_ERROR).unwrap_or(&STATUS_NO_ERROR_STR),
-        status_code: STATUS_NO_ERROR,
-        message: API_VERSION,
-        data: HashMap::new(),
-    };
-
-    (StatusCode::OK, Json(json_response))
-}
\ No newline at end of file
+    (StatusCode::OK, json_response)
+}
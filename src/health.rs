@@ -0,0 +1,163 @@
+//! Health of the dependencies the API needs to serve requests
+//!
+//! Every dependency is checked concurrently with a timeout. The API is ready when all its
+//! critical dependencies are up; a non-critical one being down is only reported.
+
+use std::fmt::Display;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use diesel::RunQueryDsl;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::constants::health_constants::*;
+use crate::database::with_connection_timeout;
+use crate::state::AppState;
+use crate::util::env::env_parse;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Up,
+    Down,
+}
+
+/// Result of the check of one dependency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    /// Whether the API is not ready while the component is down
+    pub critical: bool,
+    pub latency_ms: u64,
+    /// Why the component is down: `timeout` or `unavailable`. The details are logged rather
+    /// than exposed to the unauthenticated callers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Data of the readiness response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Readiness {
+    pub status: HealthStatus,
+    pub components: Vec<ComponentHealth>,
+}
+
+impl Readiness {
+    pub fn new(components: Vec<ComponentHealth>) -> Self {
+        let ready = components
+            .iter()
+            .all(|component| !component.critical || component.status == HealthStatus::Up);
+        Readiness {
+            status: if ready { HealthStatus::Up } else { HealthStatus::Down },
+            components,
+        }
+    }
+}
+
+/// Time a dependency has to answer, from `HEALTH_CHECK_TIMEOUT_MS`
+pub fn check_timeout() -> Duration {
+    Duration::from_millis(env_parse(HEALTH_CHECK_TIMEOUT_MS_ENV, DEFAULT_HEALTH_CHECK_TIMEOUT_MS))
+}
+
+/// Check the dependency `name` by running `check` within `timeout`
+pub async fn check_component<T, E: Display>(
+    name: &str,
+    critical: bool,
+    timeout: Duration,
+    check: impl Future<Output = Result<T, E>>,
+) -> ComponentHealth {
+    let start = Instant::now();
+    let result = tokio::time::timeout(timeout, check).await;
+    let latency_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+    let error = match result {
+        Ok(Ok(_)) => None,
+        Ok(Err(err)) => {
+            warn!("health check of {} failed: {}", name, err);
+            Some("unavailable")
+        }
+        Err(_) => {
+            warn!("health check of {} timed out after {:?}", name, timeout);
+            Some("timeout")
+        }
+    };
+    ComponentHealth {
+        name: name.to_string(),
+        status: if error.is_none() { HealthStatus::Up } else { HealthStatus::Down },
+        critical,
+        latency_ms,
+        error: error.map(str::to_string),
+    }
+}
+
+/// Check every dependency of the API concurrently.
+///
+/// The database check also waits at most `timeout` for a pooled connection, so the blocking task
+/// does not outlive the check while the pool is exhausted.
+pub async fn readiness(state: &AppState) -> Readiness {
+    let timeout = check_timeout();
+    let (database, storage) = tokio::join!(
+        check_component(
+            "postgres",
+            true,
+            timeout,
+            with_connection_timeout(&state.db_pool, timeout, |conn| {
+                diesel::sql_query("SELECT 1").execute(conn)
+            }),
+        ),
+        check_component("storage", true, timeout, state.s3_client.head_bucket()),
+    );
+    Readiness::new(vec![database, storage])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_component() {
+        let timeout = Duration::from_millis(50);
+
+        let up = check_component("cache", false, timeout, async { Ok::<_, String>(()) }).await;
+        assert_eq!(up.status, HealthStatus::Up);
+        assert_eq!(up.error, None);
+
+        let down = check_component("cache", false, timeout, async { Err::<(), _>("refused") }).await;
+        assert_eq!(down.status, HealthStatus::Down);
+        assert_eq!(down.error.as_deref(), Some("unavailable"));
+
+        let slow = check_component("cache", false, timeout, async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<_, String>(())
+        })
+        .await;
+        assert_eq!(slow.error.as_deref(), Some("timeout"));
+        assert!(slow.latency_ms < 5000);
+    }
+
+    #[test]
+    fn test_readiness_status() {
+        let component = |name: &str, critical, status| ComponentHealth {
+            name: name.to_string(),
+            status,
+            critical,
+            latency_ms: 1,
+            error: None,
+        };
+
+        let degraded = Readiness::new(vec![
+            component("postgres", true, HealthStatus::Up),
+            component("syslog", false, HealthStatus::Down),
+        ]);
+        assert_eq!(degraded.status, HealthStatus::Up);
+
+        let down = Readiness::new(vec![
+            component("postgres", true, HealthStatus::Down),
+            component("storage", true, HealthStatus::Up),
+        ]);
+        assert_eq!(down.status, HealthStatus::Down);
+        assert_eq!(serde_json::to_value(&down).unwrap()["status"], "down");
+    }
+}
@@ -0,0 +1,156 @@
+//! Localization of the response messages.
+//!
+//! Message catalogs are JSON bundles under `locales/`, keyed by the identifiers of the status
+//! code catalog. Messages may reference parameters as `{name}`. The locale of a request is
+//! negotiated from `Accept-Language`, or taken from the user preference carried by their
+//! token, and messages missing from its catalog fall back to English.
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use axum::http::{header, HeaderMap};
+use once_cell::sync::Lazy;
+
+use crate::response::status_code::{status_info, status_str};
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+const BUNDLES: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en.json")),
+    ("fr", include_str!("../locales/fr.json")),
+    ("vi", include_str!("../locales/vi.json")),
+];
+
+static CATALOGS: Lazy<HashMap<&'static str, HashMap<String, String>>> = Lazy::new(|| {
+    BUNDLES
+        .iter()
+        .map(|(locale, bundle)| {
+            let messages = serde_json::from_str(bundle)
+                .unwrap_or_else(|err| panic!("invalid message bundle for locale {}: {}", locale, err));
+            (*locale, messages)
+        })
+        .collect()
+});
+
+tokio::task_local! {
+    static LOCALE: &'static str;
+}
+
+/// Run `f` with `locale` as the locale of the messages it builds
+pub async fn with_locale<F: Future>(locale: &'static str, f: F) -> F::Output {
+    LOCALE.scope(locale, f).await
+}
+
+/// Locale negotiated for the current request
+pub fn current_locale() -> &'static str {
+    LOCALE.try_with(|locale| *locale).unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Supported locale matching a language tag, by its primary subtag (`fr-CA` matches `fr`)
+pub fn supported_locale(tag: &str) -> Option<&'static str> {
+    let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+    BUNDLES.iter().map(|(locale, _)| *locale).find(|locale| *locale == primary)
+}
+
+/// Preferred supported locale of the `Accept-Language` header, by quality then order
+pub fn negotiate_locale(headers: &HeaderMap) -> &'static str {
+    let mut best: Option<(&'static str, f32)> = None;
+    let ranges = headers
+        .get_all(header::ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    for range in ranges {
+        let mut params = range.split(';').map(str::trim);
+        let tag = params.next().unwrap_or_default();
+        let quality = params
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if let Some(locale) = supported_locale(tag) {
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((locale, quality));
+            }
+        }
+    }
+    best.map_or(DEFAULT_LOCALE, |(locale, _)| locale)
+}
+
+/// Message `key` in the current locale, falling back to English
+pub fn translate(key: &str, params: &[(&str, String)]) -> Option<String> {
+    let message = [current_locale(), DEFAULT_LOCALE]
+        .iter()
+        .find_map(|locale| CATALOGS.get(locale)?.get(key))?;
+    Some(interpolate(message, params))
+}
+
+/// Localized message of an application status code
+pub fn status_message(status_code: u16, params: &[(&str, String)]) -> String {
+    status_info(status_code)
+        .and_then(|info| translate(info.id, params))
+        .unwrap_or(status_str(status_code).to_string())
+}
+
+/// Replace the `{name}` placeholders of `template`. Unknown placeholders are kept.
+fn interpolate(template: &str, params: &[(&str, String)]) -> String {
+    let mut message = template.to_string();
+    for (name, value) in params {
+        message = message.replace(&format!("{{{}}}", name), value);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    use crate::response::status_code::*;
+
+    fn accept_language(value: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(header::ACCEPT_LANGUAGE, HeaderValue::from_static(value))])
+    }
+
+    #[test]
+    fn test_bundles_cover_the_status_codes() {
+        for (locale, _) in BUNDLES {
+            let catalog = &CATALOGS[locale];
+            for info in STATUS_CODES {
+                assert!(catalog.contains_key(info.id), "{} has no message for {}", locale, info.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_negotiate_locale() {
+        assert_eq!(negotiate_locale(&HeaderMap::new()), DEFAULT_LOCALE);
+        assert_eq!(negotiate_locale(&accept_language("fr-CA")), "fr");
+        assert_eq!(negotiate_locale(&accept_language("de, vi;q=0.8, fr;q=0.5")), "vi");
+        assert_eq!(negotiate_locale(&accept_language("fr;q=0, de")), DEFAULT_LOCALE);
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(
+            interpolate("{count} of {total} fields", &[("count", "2".to_string()), ("total", "5".to_string())]),
+            "2 of 5 fields"
+        );
+        assert_eq!(interpolate("{missing}", &[]), "{missing}");
+    }
+
+    #[tokio::test]
+    async fn test_status_message() {
+        let params = [("limit", "1024".to_string())];
+        assert_eq!(
+            status_message(STATUS_PAYLOAD_TOO_LARGE, &params),
+            "The request exceeds the maximum size of 1024 bytes"
+        );
+
+        let message = with_locale("fr", async { status_message(STATUS_PAYLOAD_TOO_LARGE, &params) }).await;
+        assert_eq!(message, "La requête dépasse la taille maximale de 1024 octets");
+
+        assert_eq!(status_message(999, &[]), STATUS_INTERNAL_SERVER_ERROR_STR);
+    }
+}
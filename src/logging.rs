@@ -1,7 +1,12 @@
-use crate::logging::ecs_logger::Event;
-use crate::logging::extra_fields::merge_extra_fields;
-
 pub mod ecs_logger;
+pub mod error_fields;
+pub mod log_level;
+pub mod request_context;
 mod timestamp;
 mod extra_fields;
+mod metadata;
+mod redaction;
+mod sampling;
+mod tracing_layer;
+pub mod writer;
 
@@ -4,14 +4,15 @@
 //!
 //! ## Example
 //!
-//! ```
-//! use ecs_logger::ecs::{Event, LogOrigin, LogOriginFile, LogOriginRust};
+//! ```ignore
+//! use crate::logging::ecs_logger::{Event, LogOrigin, LogOriginFile, LogOriginRust};
+//! use crate::logging::metadata::get_metadata;
 //!
 //! let event = Event {
 //!     timestamp: chrono::Utc::now(),
 //!     log_level: "ERROR",
 //!     message: "Error!".to_string(),
-//!     ecs_version: "1.12.1",
+//!     ecs_version: "8.11.0",
 //!     log_origin: LogOrigin {
 //!         file: LogOriginFile {
 //!             line: Some(144),
@@ -23,6 +24,7 @@
 //!             file_path: Some("src/server.rs"),
 //!         },
 //!     },
+//!     metadata: get_metadata(),
 //! };
 //!
 //! println!("{}", serde_json::to_string(&event).unwrap());
@@ -30,21 +32,82 @@
 
 use std::borrow::BorrowMut;
 use chrono::{DateTime, Utc};
+use log::kv;
 use serde::Serialize;
+use serde_json::{Map, Value};
 use std::path::Path;
 use crate::logging::extra_fields::merge_extra_fields;
+use crate::logging::log_level;
+use crate::logging::metadata::{get_metadata, Metadata};
+use crate::logging::redaction::redact;
+use crate::logging::sampling::{self, Callsite};
 use crate::logging::timestamp;
+use crate::logging::tracing_layer;
+use crate::logging::writer::{self, WorkerGuard, WriterConfig};
 
 /// Represents Elastic Common Schema version.
-const ECS_VERSION: &str = "1.12.1";
+const ECS_VERSION: &str = "8.11.0";
+
+/// Install the ECS formatter as the global logger, and the [`EcsLayer`](tracing_layer::EcsLayer)
+/// as the global `tracing` subscriber, both writing the same stream of events to the sinks
+/// configured by the environment. The returned guard flushes the events when dropped.
+///
+/// # Panics
+///
+/// Panics if a logger or a subscriber was already installed.
+pub fn init() -> WorkerGuard {
+    let (writer, guard) = writer::non_blocking(WriterConfig::from_env());
+    try_init(writer.clone()).expect("ecs_logger::init should not be called after the logger is initialized");
+    tracing_layer::try_init(writer).expect("ecs_logger::init should not be called after a tracing subscriber is set");
+    sampling::spawn_summaries();
+    guard
+}
 
-pub fn try_init() -> Result<(), log::SetLoggerError> {
-    env_logger::builder().format(format).try_init()
+pub fn try_init(writer: impl std::io::Write + Send + 'static) -> Result<(), log::SetLoggerError> {
+    let logger = env_logger::Builder::new()
+        .format(format)
+        .target(env_logger::Target::Pipe(Box::new(writer)))
+        .filter_level(log::LevelFilter::Trace)
+        .build();
+    log::set_boxed_logger(Box::new(EcsLogger(logger)))?;
+    log::set_max_level(log_level::max_level());
+    Ok(())
+}
+
+/// Logger filtering the records by the [runtime level directives](log_level) and
+/// [sampling](sampling) before they are formatted
+struct EcsLogger(env_logger::Logger);
+
+impl log::Log for EcsLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        log_level::enabled(metadata.target(), metadata.level())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata())
+            && sampling::admit(Callsite::of(record), || record.args().to_string())
+        {
+            self.0.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
 }
 
 pub fn format(buf: &mut impl std::io::Write, record: &log::Record) -> std::io::Result<()> {
     let event = Event::new(timestamp::get_timestamp(), record);
+    write_event(buf, &event, &key_values(record))
+}
 
+/// Write `event` as a line of JSON, along with the extra fields in scope and the `fields` of
+/// the call, once its sensitive data is masked
+pub(crate) fn write_event(
+    buf: &mut impl std::io::Write,
+    event: &Event,
+    fields: &Map<String, Value>,
+) -> std::io::Result<()> {
     let event_json_value =
         serde_json::to_value(event).expect("Event should be converted into JSON");
     let event_json_map = match event_json_value {
@@ -52,7 +115,8 @@ pub fn format(buf: &mut impl std::io::Write, record: &log::Record) -> std::io::R
         _ => unreachable!("Event should be converted into a JSON object"),
     };
 
-    let merged_json_map = merge_extra_fields(event_json_map);
+    let mut merged_json_map = merge_extra_fields(event_json_map, fields);
+    redact(&mut merged_json_map);
 
     serde_json::to_writer(buf.borrow_mut(), &merged_json_map)?;
     writeln!(buf)?;
@@ -60,6 +124,33 @@ pub fn format(buf: &mut impl std::io::Write, record: &log::Record) -> std::io::R
     Ok(())
 }
 
+/// Key-values of the log call, such as `user.id` in `log::info!(user.id = 42; "...")`
+fn key_values(record: &log::Record) -> Map<String, Value> {
+    struct Collect(Map<String, Value>);
+
+    impl<'kvs> kv::Visitor<'kvs> for Collect {
+        fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+            let value = if let Some(v) = value.to_bool() {
+                Value::from(v)
+            } else if let Some(v) = value.to_i64() {
+                Value::from(v)
+            } else if let Some(v) = value.to_u64() {
+                Value::from(v)
+            } else if let Some(v) = value.to_f64() {
+                Value::from(v)
+            } else {
+                Value::from(value.to_string())
+            };
+            self.0.insert(key.as_str().to_string(), value);
+            Ok(())
+        }
+    }
+
+    let mut collect = Collect(Map::new());
+    let _ = record.key_values().visit(&mut collect);
+    collect.0
+}
+
 /// Representation of an event compatible with ECS logging.
 ///
 /// The event follows [ECS Logging spec](https://github.com/elastic/ecs-logging/tree/master/spec).
@@ -93,6 +184,12 @@ pub struct Event<'a> {
     /// Mapped to `log.origin` field.
     #[serde(rename = "log.origin")]
     pub log_origin: LogOrigin<'a>,
+
+    /// Service, host and process which logged the message.
+    ///
+    /// Mapped to `service.*`, `host.hostname` and `process.pid` fields.
+    #[serde(flatten)]
+    pub metadata: &'a Metadata,
 }
 
 /// Information about the source code which logged the message.
@@ -173,8 +270,68 @@ impl<'a> Event<'a> {
                     file_path: record.file(),
                 },
             },
+            metadata: get_metadata(),
         }
     }
+
+    /// Creates ECS log event from the [`tracing::Metadata`] of a `tracing` event.
+    pub fn from_tracing(timestamp: DateTime<Utc>, metadata: &tracing::Metadata<'a>, message: String) -> Self {
+        let file_path = metadata.file().map(Path::new);
+
+        Event {
+            timestamp,
+            log_level: metadata.level().as_str(),
+            message,
+            ecs_version: ECS_VERSION,
+            log_origin: LogOrigin {
+                file: LogOriginFile {
+                    line: metadata.line(),
+                    name: file_path
+                        .and_then(|p| p.file_name())
+                        .and_then(|os_str| os_str.to_str()),
+                },
+                rust: LogOriginRust {
+                    target: metadata.target(),
+                    module_path: metadata.module_path(),
+                    file_path: metadata.file(),
+                },
+            },
+            metadata: get_metadata(),
+        }
+    }
+}
+
+/// Events written so far by the global logger, which is installed on the first call, for the
+/// tests of the events logged as a side effect
+#[cfg(test)]
+pub(crate) fn captured_events() -> Vec<Value> {
+    use once_cell::sync::Lazy;
+    use std::sync::{Arc, Mutex};
+
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    static CAPTURED: Lazy<Arc<Mutex<Vec<u8>>>> = Lazy::new(|| {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        try_init(Capture(buf.clone())).expect("no other logger is installed by the tests");
+        buf
+    });
+
+    let output = CAPTURED.lock().unwrap();
+    output
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect()
 }
 
 #[cfg(test)]
@@ -204,7 +361,7 @@ mod tests {
                 timestamp,
                 log_level: "ERROR",
                 message: "Error!".to_string(),
-                ecs_version: "1.12.1",
+                ecs_version: "8.11.0",
                 log_origin: LogOrigin {
                     file: LogOriginFile {
                         line: Some(144),
@@ -215,7 +372,8 @@ mod tests {
                         module_path: Some("my_app::server"),
                         file_path: Some("src/server.rs")
                     }
-                }
+                },
+                metadata: get_metadata(),
             }
         );
     }
@@ -230,7 +388,7 @@ mod tests {
             timestamp,
             log_level: "TRACE",
             message: "tracing msg".to_string(),
-            ecs_version: "1.12.1",
+            ecs_version: "8.11.0",
             log_origin: LogOrigin {
                 file: LogOriginFile {
                     line: Some(1234),
@@ -242,11 +400,12 @@ mod tests {
                     file_path: Some("src/path/to/your/file.rs"),
                 },
             },
+            metadata: get_metadata(),
         };
 
         assert_eq!(
             serde_json::to_string(&event).expect("Failed to serialize ECS event"),
-            r#"{"@timestamp":"2021-11-24T17:38:21.000098765Z","log.level":"TRACE","message":"tracing msg","ecs.version":"1.12.1","log.origin":{"file":{"line":1234,"name":"file.rs"},"rust":{"target":"myCustomTarget123","module_path":"my_app::path::to::your::file","file_path":"src/path/to/your/file.rs"}}}"#
+            r#"{"@timestamp":"2021-11-24T17:38:21.000098765Z","log.level":"TRACE","message":"tracing msg","ecs.version":"8.11.0","log.origin":{"file":{"line":1234,"name":"file.rs"},"rust":{"target":"myCustomTarget123","module_path":"my_app::path::to::your::file","file_path":"src/path/to/your/file.rs"}},"service.name":"axum_api","service.version":"0.1.0","service.environment":"test","host.hostname":"test-host","process.pid":4242}"#
         );
     }
 
@@ -260,7 +419,7 @@ mod tests {
             timestamp,
             log_level: "TRACE",
             message: "tracing msg".to_string(),
-            ecs_version: "1.12.1",
+            ecs_version: "8.11.0",
             log_origin: LogOrigin {
                 file: LogOriginFile {
                     line: None,
@@ -272,11 +431,76 @@ mod tests {
                     file_path: None,
                 },
             },
+            metadata: get_metadata(),
         };
 
         assert_eq!(
             serde_json::to_string(&event).expect("Failed to serialize ECS event"),
-            r#"{"@timestamp":"2021-11-24T17:38:21.000098765Z","log.level":"TRACE","message":"tracing msg","ecs.version":"1.12.1","log.origin":{"file":{},"rust":{"target":"myCustomTarget123"}}}"#
+            r#"{"@timestamp":"2021-11-24T17:38:21.000098765Z","log.level":"TRACE","message":"tracing msg","ecs.version":"8.11.0","log.origin":{"file":{},"rust":{"target":"myCustomTarget123"}},"service.name":"axum_api","service.version":"0.1.0","service.environment":"test","host.hostname":"test-host","process.pid":4242}"#
+        );
+    }
+
+    #[test]
+    fn test_format() {
+        let record = log::Record::builder()
+            .args(format_args!("listening on 0.0.0.0:3000"))
+            .level(log::Level::Info)
+            .target("axum_api")
+            .file(Some("src/main.rs"))
+            .line(Some(42))
+            .module_path(Some("axum_api"))
+            .build();
+
+        let mut buf = Vec::new();
+        format(&mut buf, &record).unwrap();
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r#"{"@timestamp":"2000-01-23T01:23:45.678901200Z","ecs.version":"8.11.0","host.hostname":"test-host","#,
+                r#""log.level":"INFO","log.origin":{"file":{"line":42,"name":"main.rs"},"rust":{"file_path":"src/main.rs","#,
+                r#""module_path":"axum_api","target":"axum_api"}},"message":"listening on 0.0.0.0:3000","process.pid":4242,"#,
+                r#""service.environment":"test","service.name":"axum_api","service.version":"0.1.0"}"#,
+                "\n"
+            )
         );
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_format_with_request_id() {
+        let record = log::Record::builder()
+            .args(format_args!("handled"))
+            .level(log::Level::Info)
+            .target("myApp")
+            .build();
+
+        let mut buf = Vec::new();
+        crate::logging::request_context::with_request_id("req-42".to_string(), async {
+            format(&mut buf, &record).unwrap();
+        })
+        .await;
+
+        let event: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(event["trace.id"], "req-42");
+        assert_eq!(event["http.request.id"], "req-42");
+        assert_eq!(event["message"], "handled");
+    }
+
+    #[test]
+    fn test_key_values() {
+        let kvs: Vec<(&str, kv::Value)> = vec![
+            ("user.id", kv::Value::from("42")),
+            ("http.response.status_code", kv::Value::from(404u16)),
+            ("cache.hit", kv::Value::from(false)),
+        ];
+        let record = log::Record::builder()
+            .args(format_args!("served"))
+            .key_values(&kvs)
+            .build();
+
+        let fields = key_values(&record);
+        assert_eq!(fields["user.id"], "42");
+        assert_eq!(fields["http.response.status_code"], 404);
+        assert_eq!(fields["cache.hit"], false);
+    }
+}
@@ -0,0 +1,130 @@
+//! ECS `error.*` fields of the errors being logged
+//!
+//! ```ignore
+//! with_error_fields(&err, || log::error!("failed to sign the token: {}", err));
+//! ```
+//!
+//! adds `error.type`, `error.message`, `error.chain` (the messages of the `source()` chain,
+//! outermost first) and, when backtraces are enabled by `RUST_LIB_BACKTRACE` or
+//! `RUST_BACKTRACE`, `error.stack_trace` to the event. The stack trace is the one `anyhow`
+//! captured where the error was created or converted into an `anyhow::Error`.
+
+use std::backtrace::BacktraceStatus;
+use std::error::Error;
+
+use serde_json::{json, Map, Value};
+
+use crate::database::DbError;
+use crate::logging::extra_fields::with_extra_fields_sync;
+use crate::upload::UploadError;
+
+/// Type reported for errors of no known type, such as `anyhow!` messages
+const UNKNOWN_ERROR_TYPE: &str = "anyhow::Error";
+
+/// Run `log`, which logs `err`, with the ECS fields describing `err` added to its events
+pub fn with_error_fields<R>(err: &anyhow::Error, log: impl FnOnce() -> R) -> R {
+    with_extra_fields_sync(error_fields(err), log).expect("error fields are an object")
+}
+
+/// ECS fields describing `err` and its causes
+pub fn error_fields(err: &anyhow::Error) -> Map<String, Value> {
+    let chain: Vec<String> = err.chain().map(ToString::to_string).collect();
+    let mut fields = json!({
+        "error.type": error_type(err),
+        "error.message": err.to_string(),
+        "error.chain": chain,
+    });
+
+    let backtrace = err.backtrace();
+    if backtrace.status() == BacktraceStatus::Captured {
+        fields["error.stack_trace"] = Value::from(backtrace.to_string());
+    }
+    match fields {
+        Value::Object(fields) => fields,
+        _ => unreachable!("the error fields are an object"),
+    }
+}
+
+/// Name of the type of the innermost error of the chain whose type is known
+fn error_type(err: &anyhow::Error) -> &'static str {
+    let chain: Vec<&(dyn Error + 'static)> = err.chain().collect();
+    chain
+        .into_iter()
+        .rev()
+        .find_map(known_error_type)
+        .unwrap_or(UNKNOWN_ERROR_TYPE)
+}
+
+fn known_error_type(err: &(dyn Error + 'static)) -> Option<&'static str> {
+    let name = if err.is::<std::io::Error>() {
+        "std::io::Error"
+    } else if err.is::<std::num::ParseIntError>() {
+        "std::num::ParseIntError"
+    } else if err.is::<serde_json::Error>() {
+        "serde_json::Error"
+    } else if err.is::<diesel::result::Error>() {
+        "diesel::result::Error"
+    } else if err.is::<diesel::r2d2::PoolError>() {
+        "diesel::r2d2::PoolError"
+    } else if err.is::<jsonwebtoken::errors::Error>() {
+        "jsonwebtoken::errors::Error"
+    } else if err.is::<tokio::task::JoinError>() {
+        "tokio::task::JoinError"
+    } else if err.is::<image::ImageError>() {
+        "image::ImageError"
+    } else if err.is::<DbError>() {
+        "DbError"
+    } else if err.is::<UploadError>() {
+        "UploadError"
+    } else {
+        return None;
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    use crate::logging::extra_fields::merge_extra_fields;
+
+    fn parse_error() -> anyhow::Error {
+        "x".parse::<u8>().context("failed to read the port").unwrap_err()
+    }
+
+    #[test]
+    fn test_error_fields() {
+        let fields = error_fields(&parse_error());
+
+        assert_eq!(fields["error.type"], "std::num::ParseIntError");
+        assert_eq!(fields["error.message"], "failed to read the port");
+        assert_eq!(
+            fields["error.chain"],
+            json!(["failed to read the port", "invalid digit found in string"])
+        );
+    }
+
+    #[test]
+    fn test_error_type() {
+        let err = anyhow::anyhow!("token expired");
+        assert_eq!(error_type(&err), UNKNOWN_ERROR_TYPE);
+
+        let io = std::io::Error::other("disk full");
+        assert_eq!(error_type(&anyhow::Error::new(io)), "std::io::Error");
+        let os = std::io::Error::from_raw_os_error(2);
+        assert_eq!(error_type(&anyhow::Error::new(os).context("failed to spool")), "std::io::Error");
+
+        let upload = UploadError::QuotaExceeded;
+        assert_eq!(error_type(&anyhow::Error::new(upload)), "UploadError");
+        let upload = UploadError::Io(std::io::Error::other("disk full"));
+        assert_eq!(error_type(&anyhow::Error::new(upload)), "std::io::Error");
+    }
+
+    #[test]
+    fn test_with_error_fields() {
+        let merged = with_error_fields(&parse_error(), || merge_extra_fields(Map::new(), &Map::new()));
+        assert_eq!(merged["error.message"], "failed to read the port");
+        assert_eq!(merged["error.type"], "std::num::ParseIntError");
+    }
+}
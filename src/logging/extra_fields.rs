@@ -24,8 +24,23 @@
 //!
 //! extra_fields::clear_extra_fields();
 //! ```
+//!
+//! Fields set with [`set_extra_fields`] are global defaults. [`with_extra_fields`] layers
+//! more fields for the duration of a future, such as the handling of a request, without
+//! affecting the other tasks. Scopes nest, the innermost field winning. Finally the key-values
+//! of the log call itself (`log::info!(user_id = 42; "...")`) take precedence over both.
+//!
+//! ```ignore
+//! use crate::logging::extra_fields::with_extra_fields;
+//!
+//! # async fn handle() {}
+//! # async fn example() {
+//! with_extra_fields(serde_json::json!({ "user.id": "42" }), handle()).await.unwrap();
+//! # }
+//! ```
 
 use serde_json::{Map, Value};
+use std::future::Future;
 use std::sync::RwLock;
 use thiserror::Error;
 
@@ -33,7 +48,11 @@ type JsonMap = Map<String, Value>;
 
 static EXTRA_FIELDS: RwLock<Option<JsonMap>> = RwLock::new(None);
 
-/// Error returned by [`set_extra_fields`].
+tokio::task_local! {
+    static SCOPED_EXTRA_FIELDS: JsonMap;
+}
+
+/// Error returned by [`set_extra_fields`] and [`with_extra_fields`].
 #[derive(Error, Debug)]
 pub enum SetExtraFieldsError {
     /// The data cannot be converted into JSON.
@@ -87,20 +106,65 @@ pub enum SetExtraFieldsError {
 /// }).unwrap();
 /// ```
 pub fn set_extra_fields(extra_fields: impl serde::Serialize) -> Result<(), SetExtraFieldsError> {
-    let v = serde_json::to_value(extra_fields)?;
-    let json_map = match v {
-        Value::Object(m) => Some(m),
-        _ => return Err(SetExtraFieldsError::NotObject),
-    };
+    let json_map = to_json_map(extra_fields)?;
 
     {
         let mut w = EXTRA_FIELDS.write().unwrap();
-        *w = json_map;
+        *w = Some(json_map);
     }
 
     Ok(())
 }
 
+/// Add extra fields to the log records emitted while `f` runs.
+///
+/// The fields are deep merged on top of the global ones and of the enclosing scopes. They are
+/// held by the task polling `f`, so tasks spawned from it do not inherit them.
+///
+/// # Example
+///
+/// ```ignore
+/// use crate::logging::extra_fields::with_extra_fields;
+///
+/// # async fn example() {
+/// with_extra_fields(serde_json::json!({ "job.id": 7 }), async {
+///     log::info!("processing");
+/// })
+/// .await
+/// .unwrap();
+/// # }
+/// ```
+pub async fn with_extra_fields<F: Future>(
+    extra_fields: impl serde::Serialize,
+    f: F,
+) -> Result<F::Output, SetExtraFieldsError> {
+    let scoped = nested_scope(to_json_map(extra_fields)?);
+    Ok(SCOPED_EXTRA_FIELDS.scope(scoped, f).await)
+}
+
+/// Synchronous counterpart of [`with_extra_fields`], for the records emitted while `f` runs
+pub fn with_extra_fields_sync<R>(
+    extra_fields: impl serde::Serialize,
+    f: impl FnOnce() -> R,
+) -> Result<R, SetExtraFieldsError> {
+    let scoped = nested_scope(to_json_map(extra_fields)?);
+    Ok(SCOPED_EXTRA_FIELDS.sync_scope(scoped, f))
+}
+
+/// Fields of the current scope with `extra_fields` layered on top
+fn nested_scope(extra_fields: JsonMap) -> JsonMap {
+    let mut scoped = SCOPED_EXTRA_FIELDS.try_with(Clone::clone).unwrap_or_default();
+    extend_json_map(&mut scoped, &extra_fields);
+    scoped
+}
+
+fn to_json_map(extra_fields: impl serde::Serialize) -> Result<JsonMap, SetExtraFieldsError> {
+    match serde_json::to_value(extra_fields)? {
+        Value::Object(m) => Ok(m),
+        _ => Err(SetExtraFieldsError::NotObject),
+    }
+}
+
 /// Clear all extra fields previously set by [`set_extra_fields`].
 ///
 /// # Example
@@ -125,13 +189,18 @@ pub fn clear_extra_fields() {
     *w = None;
 }
 
-/// Deep merge extra fields into `json_map`
-pub(crate) fn merge_extra_fields(mut json_map: JsonMap) -> JsonMap {
-    let r = EXTRA_FIELDS.read().unwrap();
-    if let Some(extra_fields) = &*r {
-        extend_json_map(&mut json_map, extra_fields);
+/// Deep merge the global, scoped and per-call extra fields into `json_map`, in that order
+pub(crate) fn merge_extra_fields(mut json_map: JsonMap, per_call_fields: &JsonMap) -> JsonMap {
+    {
+        let r = EXTRA_FIELDS.read().unwrap();
+        if let Some(extra_fields) = &*r {
+            extend_json_map(&mut json_map, extra_fields);
+        }
     }
 
+    let _ = SCOPED_EXTRA_FIELDS.try_with(|scoped| extend_json_map(&mut json_map, scoped));
+    extend_json_map(&mut json_map, per_call_fields);
+
     json_map
 }
 
@@ -155,9 +224,18 @@ mod tests {
     use super::*;
     use serde_json::json;
     use std::collections::BTreeMap;
+    use std::sync::{Mutex, MutexGuard};
+
+    /// Serializes the tests which set the global extra fields
+    static GLOBAL_FIELDS_LOCK: Mutex<()> = Mutex::new(());
+
+    fn lock_global_fields() -> MutexGuard<'static, ()> {
+        GLOBAL_FIELDS_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+    }
 
     #[test]
     fn test_set_extra_fields_ok() {
+        let _lock = lock_global_fields();
         set_extra_fields(json!({
             "a": 1,
             "b": {
@@ -182,6 +260,7 @@ mod tests {
 
     #[test]
     fn test_set_extra_fields_err() {
+        let _lock = lock_global_fields();
         let mut map = BTreeMap::new();
         map.insert(vec![32, 64], "x86");
         assert!(matches!(
@@ -197,6 +276,7 @@ mod tests {
 
     #[test]
     fn test_clear_extra_fields() {
+        let _lock = lock_global_fields();
         set_extra_fields(json!({
             "a": 1,
             "b": {
@@ -213,6 +293,7 @@ mod tests {
 
     #[test]
     fn test_merge_extra_fields() {
+        let _lock = lock_global_fields();
         set_extra_fields(json!({
             "b": {
                 "d": 3,
@@ -227,7 +308,7 @@ mod tests {
                 "c": 2,
             },
         });
-        let a_with_extra_fields = merge_extra_fields(a.as_object_mut().unwrap().clone());
+        let a_with_extra_fields = merge_extra_fields(a.as_object_mut().unwrap().clone(), &Map::new());
 
         assert_eq!(
             serde_json::to_string(&Value::Object(a_with_extra_fields)).unwrap(),
@@ -276,4 +357,54 @@ mod tests {
                 .to_string()
         );
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_with_extra_fields_nests() {
+        let per_call = json!({ "c": 3 });
+        let merged = with_extra_fields(json!({ "a": 1, "b": { "x": 1 } }), async {
+            with_extra_fields(json!({ "a": 2, "b": { "y": 2 } }), async {
+                merge_extra_fields(Map::new(), per_call.as_object().unwrap())
+            })
+            .await
+            .unwrap()
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(merged["a"], 2);
+        assert_eq!(merged["b"]["x"], 1);
+        assert_eq!(merged["b"]["y"], 2);
+        assert_eq!(merged["c"], 3);
+    }
+
+    #[tokio::test]
+    async fn test_with_extra_fields_is_task_local() {
+        let other_task = tokio::spawn(async {
+            SCOPED_EXTRA_FIELDS.try_with(|scoped| scoped.clone()).ok()
+        });
+        with_extra_fields(json!({ "a": 1 }), async {
+            assert_eq!(other_task.await.unwrap(), None);
+        })
+        .await
+        .unwrap();
+
+        assert!(SCOPED_EXTRA_FIELDS.try_with(|_| ()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_per_call_fields_take_precedence() {
+        let merged = with_extra_fields(json!({ "a": 1, "b": 1 }), async {
+            merge_extra_fields(Map::new(), json!({ "b": 2 }).as_object().unwrap())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(merged["a"], 1);
+        assert_eq!(merged["b"], 2);
+
+        assert!(matches!(
+            with_extra_fields(1, async {}).await,
+            Err(SetExtraFieldsError::NotObject)
+        ));
+    }
+}
@@ -0,0 +1,277 @@
+//! Level directives of the log events, changeable at runtime
+//!
+//! Directives follow the `RUST_LOG` syntax of env_logger, without its message filter: a default
+//! level and per-target levels, such as `info,axum_api::upload=debug,access=off`. The level of
+//! the most specific target applies. They apply to the `log` records and the `tracing` events
+//! alike, and start from `RUST_LOG`.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::{Level, LevelFilter, Record};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::constants::logging_constants::*;
+use crate::logging::extra_fields::with_extra_fields_sync;
+use crate::logging::sampling::SUMMARY_TARGET;
+use crate::util::env::env_parse;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("invalid log level directive `{0}`")]
+pub struct DirectiveError(String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directives {
+    default: LevelFilter,
+    /// Per-target levels, most specific target first
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Directives {
+    /// Level enabled for the events of `target`
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target.strip_prefix(prefix.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Most verbose level enabled for any target
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets.iter().map(|(_, level)| *level).fold(self.default, Ord::max)
+    }
+}
+
+impl FromStr for Directives {
+    type Err = DirectiveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut directives = Directives {
+            default: LevelFilter::Error,
+            targets: Vec::new(),
+        };
+        for directive in s.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+            let invalid = || DirectiveError(directive.to_string());
+            match directive.split_once('=') {
+                Some((target, level)) if !target.trim().is_empty() => {
+                    let level = level.trim().parse().map_err(|_| invalid())?;
+                    directives.targets.push((target.trim().to_string(), level));
+                }
+                Some(_) => return Err(invalid()),
+                None => match directive.parse() {
+                    Ok(level) => directives.default = level,
+                    // A bare target enables all of its events
+                    Err(_) => directives.targets.push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        directives.targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(directives)
+    }
+}
+
+impl fmt::Display for Directives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.default.as_str().to_ascii_lowercase())?;
+        for (target, level) in &self.targets {
+            write!(f, ",{}={}", target, level.as_str().to_ascii_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+/// Directives in effect, as reported by the admin endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LogLevel {
+    pub directives: String,
+    /// When the directives are reverted to `revert_to`, for temporary changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revert_to: Option<String>,
+}
+
+struct State {
+    directives: Directives,
+    revert: Option<(Directives, DateTime<Utc>)>,
+    /// Incremented by every change, so a pending revert knows when it was superseded
+    generation: u64,
+}
+
+static STATE: Lazy<RwLock<State>> = Lazy::new(|| {
+    let directives = env_parse(
+        LOG_LEVEL_ENV,
+        DEFAULT_LOG_LEVEL.parse().expect("the default directives are valid"),
+    );
+    RwLock::new(State {
+        directives,
+        revert: None,
+        generation: 0,
+    })
+});
+
+/// Targets whose every event is logged: a change of the directives is audited even when it
+/// turns the logs off, and the summaries of the sampled events have the level of these events
+const UNFILTERED_TARGETS: [&str; 2] = [AUDIT_LOG_TARGET, SUMMARY_TARGET];
+
+/// Whether the events of `target` at `level` are logged
+pub fn enabled(target: &str, level: Level) -> bool {
+    UNFILTERED_TARGETS.contains(&target) || level <= STATE.read().unwrap().directives.level_for(target)
+}
+
+/// Most verbose level enabled for any target, to be set as the `log` maximum level
+pub fn max_level() -> LevelFilter {
+    STATE.read().unwrap().directives.max_level()
+}
+
+pub fn current() -> LogLevel {
+    let state = STATE.read().unwrap();
+    LogLevel {
+        directives: state.directives.to_string(),
+        revert_at: state.revert.as_ref().map(|(_, at)| *at),
+        revert_to: state.revert.as_ref().map(|(directives, _)| directives.to_string()),
+    }
+}
+
+/// Apply `directives` on behalf of `user_id`, until `revert_after` has elapsed if given. A
+/// temporary change made during another one reverts to the directives in effect before both.
+pub fn set(directives: Directives, revert_after: Option<Duration>, user_id: &str) {
+    let (previous, generation) = {
+        let mut state = STATE.write().unwrap();
+        let previous = std::mem::replace(&mut state.directives, directives.clone());
+        let revert_to = state.revert.take().map_or(previous.clone(), |(revert_to, _)| revert_to);
+        state.revert = revert_after.map(|after| (revert_to, Utc::now() + chrono::Duration::from_std(after).unwrap()));
+        state.generation += 1;
+        log::set_max_level(state.directives.max_level());
+        (previous, state.generation)
+    };
+    rebuild_interest();
+    audit("log-level-changed", &previous, &directives, Some(user_id));
+
+    if let Some(after) = revert_after {
+        tokio::spawn(async move {
+            tokio::time::sleep(after).await;
+            revert(generation);
+        });
+    }
+}
+
+/// Revert the temporary change made as `generation`, unless it was superseded since
+fn revert(generation: u64) {
+    let (previous, current) = {
+        let mut state = STATE.write().unwrap();
+        if state.generation != generation {
+            return;
+        }
+        let Some((revert_to, _)) = state.revert.take() else { return };
+        let previous = std::mem::replace(&mut state.directives, revert_to.clone());
+        state.generation += 1;
+        log::set_max_level(state.directives.max_level());
+        (previous, revert_to)
+    };
+    rebuild_interest();
+    audit("log-level-reverted", &previous, &current, None);
+}
+
+/// Have the `tracing` callsites which already fired ask the filter again whether they are
+/// enabled, as it caches the answer. Must not be called with the state locked, which the
+/// filter reads.
+fn rebuild_interest() {
+    tracing_core::callsite::rebuild_interest_cache();
+}
+
+fn audit(action: &str, previous: &Directives, current: &Directives, user_id: Option<&str>) {
+    let mut fields = Map::new();
+    fields.insert("event.action".to_string(), Value::from(action));
+    fields.insert("event.category".to_string(), Value::from("configuration"));
+    fields.insert("log.directives.previous".to_string(), Value::from(previous.to_string()));
+    fields.insert("log.directives.current".to_string(), Value::from(current.to_string()));
+    if let Some(user_id) = user_id {
+        fields.insert("user.id".to_string(), Value::from(user_id));
+    }
+    // Logged through the logger directly, as the `log` macros drop the events above the maximum
+    // level the new directives may have lowered
+    with_extra_fields_sync(fields, || {
+        log::logger().log(
+            &Record::builder()
+                .args(format_args!("log level directives changed from `{}` to `{}`", previous, current))
+                .level(Level::Info)
+                .target(AUDIT_LOG_TARGET)
+                .module_path_static(Some(module_path!()))
+                .file_static(Some(file!()))
+                .line(Some(line!()))
+                .build(),
+        )
+    })
+    .expect("audit fields are an object");
+}
+
+/// Serializes the tests which change the global directives
+#[cfg(test)]
+pub(crate) static TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::ecs_logger::captured_events;
+
+    #[test]
+    fn test_parse() {
+        let directives: Directives = "warn, axum_api=info ,axum_api::upload=debug,hyper".parse().unwrap();
+        assert_eq!(directives.level_for("axum_api::upload::spool"), LevelFilter::Debug);
+        assert_eq!(directives.level_for("axum_api::routes"), LevelFilter::Info);
+        assert_eq!(directives.level_for("axum_api_other"), LevelFilter::Warn);
+        assert_eq!(directives.level_for("hyper::proto"), LevelFilter::Trace);
+        assert_eq!(directives.max_level(), LevelFilter::Trace);
+        assert_eq!(directives.to_string(), "warn,axum_api::upload=debug,axum_api=info,hyper=trace");
+
+        assert_eq!("".parse::<Directives>().unwrap().level_for("any"), LevelFilter::Error);
+        assert_eq!(
+            "info,access=loud".parse::<Directives>(),
+            Err(DirectiveError("access=loud".to_string()))
+        );
+        assert!("=debug".parse::<Directives>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_and_revert() {
+        let _lock = TEST_LOCK.lock().await;
+        let initial = current();
+        set("info,audit=info,axum_api=debug".parse().unwrap(), Some(Duration::from_millis(50)), "admin-1");
+        assert!(enabled("axum_api::upload", Level::Debug));
+        assert!(!enabled("hyper", Level::Debug));
+
+        let changed = current();
+        assert_eq!(changed.directives, "info,axum_api=debug,audit=info");
+        assert_eq!(changed.revert_to.as_deref(), Some(initial.directives.as_str()));
+        assert!(changed.revert_at.is_some());
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(current(), initial);
+    }
+
+    #[tokio::test]
+    async fn test_audit_when_logs_are_turned_off() {
+        let _lock = TEST_LOCK.lock().await;
+        let initial: Directives = current().directives.parse().unwrap();
+        captured_events();
+
+        set("error".parse().unwrap(), None, "admin-2");
+        assert!(!enabled("axum_api", Level::Warn));
+        set(initial, None, "admin-2");
+
+        let audited = captured_events()
+            .into_iter()
+            .filter(|event| event["user.id"] == "admin-2")
+            .count();
+        assert_eq!(audited, 2);
+    }
+}
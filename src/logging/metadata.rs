@@ -0,0 +1,91 @@
+//! Service, host and process metadata added to every log event
+
+use std::env;
+use std::fs;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::constants::logging_constants::{SERVICE_ENVIRONMENT_ENV, SERVICE_NAME_ENV};
+
+/// Service, host and process which logged the events.
+///
+/// <https://www.elastic.co/guide/en/ecs/current/ecs-service.html>
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Metadata {
+    /// Mapped to `service.name` field, from `SERVICE_NAME` or the crate name.
+    #[serde(rename = "service.name")]
+    pub service_name: String,
+
+    /// Mapped to `service.version` field, the version of the crate.
+    #[serde(rename = "service.version")]
+    pub service_version: &'static str,
+
+    /// Mapped to `service.environment` field, from `SERVICE_ENVIRONMENT`.
+    #[serde(rename = "service.environment", skip_serializing_if = "Option::is_none")]
+    pub service_environment: Option<String>,
+
+    /// Mapped to `host.hostname` field.
+    #[serde(rename = "host.hostname", skip_serializing_if = "Option::is_none")]
+    pub host_hostname: Option<String>,
+
+    /// Mapped to `process.pid` field.
+    #[serde(rename = "process.pid")]
+    pub process_pid: u32,
+}
+
+impl Metadata {
+    fn from_env() -> Self {
+        Metadata {
+            service_name: env::var(SERVICE_NAME_ENV).unwrap_or(env!("CARGO_PKG_NAME").to_string()),
+            service_version: env!("CARGO_PKG_VERSION"),
+            service_environment: env::var(SERVICE_ENVIRONMENT_ENV).ok().filter(|env| !env.is_empty()),
+            host_hostname: hostname(),
+            process_pid: std::process::id(),
+        }
+    }
+}
+
+/// Name of the host, from `HOSTNAME` or the kernel
+fn hostname() -> Option<String> {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+#[cfg(not(test))]
+static METADATA: Lazy<Metadata> = Lazy::new(Metadata::from_env);
+
+#[cfg(not(test))]
+pub fn get_metadata() -> &'static Metadata {
+    &METADATA
+}
+
+#[cfg(test)]
+static MOCK_METADATA: Lazy<Metadata> = Lazy::new(|| Metadata {
+    service_name: "axum_api".to_string(),
+    service_version: "0.1.0",
+    service_environment: Some("test".to_string()),
+    host_hostname: Some("test-host".to_string()),
+    process_pid: 4242,
+});
+
+#[cfg(test)]
+pub fn get_metadata() -> &'static Metadata {
+    &MOCK_METADATA
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_env() {
+        let metadata = Metadata::from_env();
+        assert_eq!(metadata.service_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(metadata.process_pid, std::process::id());
+    }
+}
@@ -0,0 +1,40 @@
+//! Context of the request being handled, added to every event logged while handling it
+//!
+//! The context is held in a task-local, so it follows the request across `.await` points but
+//! is not inherited by spawned tasks.
+
+use std::future::Future;
+
+use serde_json::json;
+
+use crate::logging::extra_fields::with_extra_fields;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Run `f` on behalf of the request identified by `request_id`, logging it as the `trace.id`
+/// and `http.request.id` of the events
+pub async fn with_request_id<F: Future>(request_id: String, f: F) -> F::Output {
+    let fields = json!({ "trace.id": request_id, "http.request.id": request_id });
+    let f = async move { with_extra_fields(fields, f).await.expect("request fields are an object") };
+    REQUEST_ID.scope(request_id, f).await
+}
+
+/// ID of the request being handled by the current task
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|request_id| request_id.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_with_request_id() {
+        assert_eq!(current_request_id(), None);
+
+        let request_id = with_request_id("req-1".to_string(), async { current_request_id() }).await;
+        assert_eq!(request_id.as_deref(), Some("req-1"));
+    }
+}
@@ -0,0 +1,331 @@
+//! Sampling and rate limiting of noisy log events
+//!
+//! Events are first sampled at the rate configured for their level, then every call site spends
+//! a token of its own bucket per event. Events which are not written are counted, and a summary
+//! of them is logged periodically for each call site. ERROR events are neither sampled nor rate
+//! limited unless configured otherwise, and the access and audit events never are. The summaries
+//! are logged whatever the level directives of their target.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::env;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{kv, Level, Record};
+use once_cell::sync::Lazy;
+
+use crate::constants::logging_constants::*;
+use crate::util::env::env_parse;
+
+/// Target of the summaries, which are neither sampled nor filtered by level themselves
+pub(crate) const SUMMARY_TARGET: &str = module_path!();
+
+/// Targets whose every event is written
+const UNSAMPLED_TARGETS: [&str; 3] = [SUMMARY_TARGET, ACCESS_LOG_TARGET, AUDIT_LOG_TARGET];
+
+static SAMPLER: Lazy<Sampler> = Lazy::new(|| Sampler::new(SamplingConfig::from_env()));
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SamplingConfig {
+    /// Share of the events kept, by level from ERROR to TRACE
+    pub rates: [f64; 5],
+    pub sample_errors: bool,
+    /// `0` disables the rate limiting
+    pub burst: u32,
+    pub per_sec: f64,
+    pub summary_interval: Duration,
+}
+
+impl SamplingConfig {
+    pub fn from_env() -> Self {
+        let mut rates = [1.0; 5];
+        for (level, rate) in env::var(LOG_SAMPLE_RATES_ENV)
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|directive| directive.split_once('='))
+        {
+            if let (Ok(level), Ok(rate)) = (level.trim().parse::<Level>(), rate.trim().parse::<f64>()) {
+                rates[level as usize - 1] = rate.clamp(0.0, 1.0);
+            }
+        }
+
+        SamplingConfig {
+            rates,
+            sample_errors: env_parse(LOG_SAMPLE_ERRORS_ENV, false),
+            burst: env_parse(LOG_RATE_LIMIT_BURST_ENV, DEFAULT_LOG_RATE_LIMIT_BURST),
+            per_sec: env_parse(LOG_RATE_LIMIT_PER_SEC_ENV, DEFAULT_LOG_RATE_LIMIT_PER_SEC),
+            summary_interval: Duration::from_secs(
+                env_parse(LOG_SUPPRESSED_SUMMARY_SECS_ENV, DEFAULT_LOG_SUPPRESSED_SUMMARY_SECS).max(1),
+            ),
+        }
+    }
+}
+
+/// Call site of log events
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Callsite<'a> {
+    pub level: Level,
+    pub target: &'a str,
+    pub file: Option<&'a str>,
+    pub line: Option<u32>,
+}
+
+impl<'a> Callsite<'a> {
+    pub fn of(record: &Record<'a>) -> Self {
+        Callsite {
+            level: record.level(),
+            target: record.target(),
+            file: record.file(),
+            line: record.line(),
+        }
+    }
+}
+
+/// Events of a call site which were not written since the last summary
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suppressed {
+    pub level: Level,
+    pub target: String,
+    pub location: String,
+    pub count: u64,
+    /// Message of the first suppressed event
+    pub message: String,
+}
+
+struct CallsiteState {
+    seen: u64,
+    tokens: f64,
+    refilled_at: Instant,
+    suppressed: Option<Suppressed>,
+}
+
+pub struct Sampler {
+    config: SamplingConfig,
+    callsites: Mutex<HashMap<u64, CallsiteState>>,
+}
+
+impl Sampler {
+    pub fn new(config: SamplingConfig) -> Self {
+        Sampler {
+            config,
+            callsites: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the event of `callsite` is written, counting it as suppressed otherwise
+    pub fn admit(&self, callsite: Callsite, now: Instant, message: impl FnOnce() -> String) -> bool {
+        let exempt = UNSAMPLED_TARGETS.contains(&callsite.target)
+            || (callsite.level == Level::Error && !self.config.sample_errors);
+        if exempt {
+            return true;
+        }
+        let rate = self.config.rates[callsite.level as usize - 1];
+        if rate >= 1.0 && self.config.burst == 0 {
+            return true;
+        }
+
+        let mut callsites = self.callsites.lock().unwrap();
+        let state = callsites.entry(key(&callsite)).or_insert_with(|| CallsiteState {
+            seen: 0,
+            tokens: self.config.burst as f64,
+            refilled_at: now,
+            suppressed: None,
+        });
+
+        // Keep the events which bring the kept count to the next integer, exactly `rate` of them
+        state.seen += 1;
+        let sampled = (state.seen as f64 * rate).floor() > ((state.seen - 1) as f64 * rate).floor();
+
+        let admitted = sampled && {
+            if self.config.burst == 0 {
+                true
+            } else {
+                let elapsed = now.saturating_duration_since(state.refilled_at).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.config.per_sec).min(self.config.burst as f64);
+                state.refilled_at = now;
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    true
+                } else {
+                    false
+                }
+            }
+        };
+
+        if !admitted {
+            let suppressed = state.suppressed.get_or_insert_with(|| Suppressed {
+                level: callsite.level,
+                target: callsite.target.to_string(),
+                location: format!("{}:{}", callsite.file.unwrap_or("<unknown>"), callsite.line.unwrap_or(0)),
+                count: 0,
+                message: message(),
+            });
+            suppressed.count += 1;
+        }
+        admitted
+    }
+
+    /// Suppressed events of every call site since the last call
+    pub fn take_suppressed(&self) -> Vec<Suppressed> {
+        let mut callsites = self.callsites.lock().unwrap();
+        callsites.values_mut().filter_map(|state| state.suppressed.take()).collect()
+    }
+}
+
+fn key(callsite: &Callsite) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    callsite.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether the event of `callsite` is written, by the sampler configured from the environment
+pub fn admit(callsite: Callsite, message: impl FnOnce() -> String) -> bool {
+    SAMPLER.admit(callsite, Instant::now(), message)
+}
+
+/// Log the summaries of the suppressed events from a background thread
+pub fn spawn_summaries() {
+    let interval = SAMPLER.config.summary_interval;
+    thread::Builder::new()
+        .name("log-sampling".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            for suppressed in SAMPLER.take_suppressed() {
+                log_summary(&suppressed);
+            }
+        })
+        .expect("the log sampling thread should start");
+}
+
+fn log_summary(suppressed: &Suppressed) {
+    let kvs: Vec<(&str, kv::Value)> = vec![
+        ("log.suppressed", kv::Value::from(suppressed.count)),
+        ("log.suppressed_target", kv::Value::from(suppressed.target.as_str())),
+    ];
+    log::logger().log(
+        &Record::builder()
+            .args(format_args!(
+                "suppressed {} similar messages from {}: {}",
+                suppressed.count, suppressed.location, suppressed.message
+            ))
+            .level(suppressed.level)
+            .target(SUMMARY_TARGET)
+            .module_path_static(Some(module_path!()))
+            .key_values(&kvs)
+            .build(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::ecs_logger::captured_events;
+    use crate::logging::log_level;
+
+    fn config() -> SamplingConfig {
+        SamplingConfig {
+            rates: [1.0; 5],
+            sample_errors: false,
+            burst: 0,
+            per_sec: 0.0,
+            summary_interval: Duration::from_secs(60),
+        }
+    }
+
+    fn callsite(level: Level, line: u32) -> Callsite<'static> {
+        Callsite {
+            level,
+            target: "axum_api::upload",
+            file: Some("src/upload.rs"),
+            line: Some(line),
+        }
+    }
+
+    fn admitted(sampler: &Sampler, callsite: Callsite, now: Instant, count: usize) -> usize {
+        (0..count)
+            .filter(|_| sampler.admit(callsite, now, || "disk almost full".to_string()))
+            .count()
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let mut config = config();
+        config.rates[Level::Debug as usize - 1] = 0.25;
+        let sampler = Sampler::new(config);
+        let now = Instant::now();
+
+        assert_eq!(admitted(&sampler, callsite(Level::Debug, 1), now, 100), 25);
+        assert_eq!(admitted(&sampler, callsite(Level::Info, 1), now, 100), 100);
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let sampler = Sampler::new(SamplingConfig { burst: 5, per_sec: 2.0, ..config() });
+        let start = Instant::now();
+
+        assert_eq!(admitted(&sampler, callsite(Level::Warn, 1), start, 8), 5);
+        assert_eq!(admitted(&sampler, callsite(Level::Warn, 2), start, 8), 5);
+        assert_eq!(admitted(&sampler, callsite(Level::Warn, 1), start + Duration::from_secs(1), 8), 2);
+        assert_eq!(admitted(&sampler, callsite(Level::Warn, 1), start + Duration::from_secs(60), 8), 5);
+    }
+
+    #[test]
+    fn test_unsampled_events() {
+        let mut config = SamplingConfig { burst: 1, per_sec: 0.0, ..config() };
+        config.rates = [0.0; 5];
+        let now = Instant::now();
+
+        assert_eq!(admitted(&Sampler::new(config.clone()), callsite(Level::Error, 1), now, 10), 10);
+        config.sample_errors = true;
+        assert_eq!(admitted(&Sampler::new(config.clone()), callsite(Level::Error, 1), now, 10), 0);
+
+        let access = Callsite { target: ACCESS_LOG_TARGET, ..callsite(Level::Info, 1) };
+        assert_eq!(admitted(&Sampler::new(config), access, now, 10), 10);
+    }
+
+    #[test]
+    fn test_take_suppressed() {
+        let sampler = Sampler::new(SamplingConfig { burst: 2, per_sec: 0.0, ..config() });
+        let now = Instant::now();
+        admitted(&sampler, callsite(Level::Warn, 7), now, 5);
+
+        let suppressed = sampler.take_suppressed();
+        assert_eq!(
+            suppressed,
+            vec![Suppressed {
+                level: Level::Warn,
+                target: "axum_api::upload".to_string(),
+                location: "src/upload.rs:7".to_string(),
+                count: 3,
+                message: "disk almost full".to_string(),
+            }]
+        );
+        assert!(sampler.take_suppressed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_summary_below_the_level_of_its_target() {
+        let _lock = log_level::TEST_LOCK.lock().await;
+        let initial: log_level::Directives = log_level::current().directives.parse().unwrap();
+        captured_events();
+
+        log_level::set("warn,axum_api::upload=debug".parse().unwrap(), None, "admin-1");
+        log_summary(&Suppressed {
+            level: Level::Debug,
+            target: "axum_api::upload".to_string(),
+            location: "src/upload.rs:12".to_string(),
+            count: 4,
+            message: "summary probe".to_string(),
+        });
+        log_level::set(initial, None, "admin-1");
+
+        let summaries = captured_events()
+            .into_iter()
+            .filter(|event| event["log.suppressed_target"] == "axum_api::upload" && event["log.suppressed"] == 4)
+            .count();
+        assert_eq!(summaries, 1);
+    }
+}
@@ -0,0 +1,348 @@
+//! Bridge of `tracing` spans and events into the ECS stream of the `log` records
+//!
+//! The fields of the spans an event is emitted in are added to it, the innermost span winning,
+//! along with the random `span.id` of the innermost span. Outside of a request, whose ID is
+//! already the `trace.id` of its events, the `trace.id` is a random ID shared by a span tree.
+
+use std::fmt;
+use std::io::{self, Write};
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::filter::{filter_fn, FilterFn};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+use crate::logging::ecs_logger::{write_event, Event};
+use crate::logging::log_level;
+use crate::logging::request_context::current_request_id;
+use crate::logging::sampling::{self, Callsite};
+use crate::logging::timestamp;
+use crate::logging::writer::NonBlocking;
+
+/// Set a subscriber writing ECS events to `writer`, as the global `tracing` subscriber. Events
+/// are filtered by the same [level directives](log_level) as the `log` records.
+pub fn try_init(writer: NonBlocking) -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
+    let subscriber = Registry::default().with(EcsLayer::new().with_writer(writer).with_filter(level_filter()));
+    tracing::subscriber::set_global_default(subscriber)
+}
+
+/// Filter of the events by the level directives. Its answers are cached by each callsite, so
+/// the directives rebuild the cache when they change.
+fn level_filter() -> FilterFn<impl Fn(&tracing::Metadata<'_>) -> bool> {
+    filter_fn(|metadata| log_level::enabled(metadata.target(), log_level_of(*metadata.level())))
+}
+
+/// [`Layer`] writing the `tracing` events as ECS events, one JSON object per line
+pub struct EcsLayer<W = fn() -> io::Stderr> {
+    make_writer: W,
+}
+
+impl EcsLayer {
+    pub fn new() -> Self {
+        EcsLayer { make_writer: io::stderr }
+    }
+}
+
+impl<W> EcsLayer<W> {
+    /// Write the events to `make_writer` instead of stderr
+    pub fn with_writer<W2>(self, make_writer: W2) -> EcsLayer<W2> {
+        EcsLayer { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for EcsLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        let trace_id = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<SpanFields>().map(|parent| parent.trace_id.clone()))
+            .unwrap_or_else(random_hex::<16>);
+        span.extensions_mut().insert(SpanFields {
+            fields,
+            trace_id,
+            span_id: random_hex::<8>(),
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(span_fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut JsonVisitor(&mut span_fields.fields));
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            let mut ids = None;
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    fields.extend(span_fields.fields.clone());
+                    ids = Some((span_fields.trace_id.clone(), span_fields.span_id.clone()));
+                }
+            }
+            if let Some((trace_id, span_id)) = ids {
+                fields.insert("span.id".to_string(), Value::from(span_id));
+                if current_request_id().is_none() {
+                    fields.insert("trace.id".to_string(), Value::from(trace_id));
+                }
+            }
+        }
+        event.record(&mut JsonVisitor(&mut fields));
+
+        let message = match fields.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(message) => message.to_string(),
+            None => String::new(),
+        };
+        let metadata = event.metadata();
+        let callsite = Callsite {
+            level: log_level_of(*metadata.level()),
+            target: metadata.target(),
+            file: metadata.file(),
+            line: metadata.line(),
+        };
+        if !sampling::admit(callsite, || message.clone()) {
+            return;
+        }
+        let ecs_event = Event::from_tracing(timestamp::get_timestamp(), metadata, message);
+
+        // Buffered so the line reaches the writer in a single write
+        let mut buf = Vec::new();
+        if write_event(&mut buf, &ecs_event, &fields).is_ok() {
+            let _ = self.make_writer.make_writer_for(metadata).write_all(&buf);
+        }
+    }
+}
+
+/// Fields recorded on a span and its identifiers, stored in its extensions. The identifiers are
+/// random rather than the span `Id`, which the registry reuses once the span is closed.
+struct SpanFields {
+    fields: Map<String, Value>,
+    /// Shared by the spans of a tree, inherited from the parent span
+    trace_id: String,
+    span_id: String,
+}
+
+/// `N` random bytes, hex encoded
+fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).expect("the system random number generator is available");
+    hex::encode(bytes)
+}
+
+/// Records the fields of spans and events as JSON values
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        self.0.insert(field.name().to_string(), Value::from(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), Value::from(format!("{:?}", value)));
+    }
+}
+
+fn log_level_of(level: tracing::Level) -> log::Level {
+    match level {
+        tracing::Level::ERROR => log::Level::Error,
+        tracing::Level::WARN => log::Level::Warn,
+        tracing::Level::INFO => log::Level::Info,
+        tracing::Level::DEBUG => log::Level::Debug,
+        tracing::Level::TRACE => log::Level::Trace,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for SharedBuf {
+        type Writer = SharedBuf;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn capture(f: impl FnOnce()) -> Vec<Value> {
+        let buf = SharedBuf::default();
+        let subscriber = Registry::default().with(EcsLayer::new().with_writer(buf.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+
+        let output = buf.0.lock().unwrap();
+        output
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_event() {
+        let events = capture(|| tracing::warn!(file.id = 42, cached = false, "variant {} missing", "thumb"));
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event["@timestamp"], timestamp::MOCK_TIMESTAMP);
+        assert_eq!(event["log.level"], "WARN");
+        assert_eq!(event["message"], "variant thumb missing");
+        assert_eq!(event["ecs.version"], "8.11.0");
+        assert_eq!(event["service.name"], "axum_api");
+        assert_eq!(event["host.hostname"], "test-host");
+        assert_eq!(event["log.origin"]["file"]["name"], "tracing_layer.rs");
+        assert_eq!(event["file.id"], 42);
+        assert_eq!(event["cached"], false);
+        assert!(event.get("span.id").is_none());
+    }
+
+    #[test]
+    fn test_span_fields() {
+        let events = capture(|| {
+            let outer = tracing::info_span!("request", user.id = "u-1", stage = "outer");
+            let _outer = outer.enter();
+            let inner = tracing::info_span!("upload", stage = "inner", file.size = tracing::field::Empty);
+            let _inner = inner.enter();
+            inner.record("file.size", 512);
+            tracing::info!("stored");
+
+            assert_ne!(outer.id(), inner.id());
+        });
+
+        let event = &events[0];
+        assert_eq!(event["user.id"], "u-1");
+        assert_eq!(event["stage"], "inner");
+        assert_eq!(event["file.size"], 512);
+        assert_ne!(event["span.id"], event["trace.id"]);
+        assert!(event["span.id"].is_string());
+    }
+
+    #[test]
+    fn test_span_ids() {
+        let events = capture(|| {
+            for _ in 0..2 {
+                let root = tracing::info_span!("request");
+                let _root = root.enter();
+                tracing::info!("received");
+                let child = tracing::info_span!("upload");
+                let _child = child.enter();
+                tracing::info!("stored");
+            }
+        });
+
+        let ids = |event: &Value| {
+            let trace_id = event["trace.id"].as_str().unwrap().to_string();
+            let span_id = event["span.id"].as_str().unwrap().to_string();
+            (trace_id, span_id)
+        };
+        let (first_trace, first_root) = ids(&events[0]);
+        let (child_trace, child_span) = ids(&events[1]);
+        let (second_trace, second_root) = ids(&events[2]);
+
+        assert_eq!(first_trace.len(), 32);
+        assert_eq!(first_root.len(), 16);
+        assert!(first_trace.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(child_trace, first_trace);
+        assert_ne!(child_span, first_root);
+        // The registry reuses the `Id` of the closed spans, but not their identifiers
+        assert_ne!(second_trace, first_trace);
+        assert_ne!(second_root, first_root);
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_the_trace_id() {
+        let buf = SharedBuf::default();
+        let subscriber = Registry::default().with(EcsLayer::new().with_writer(buf.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        crate::logging::request_context::with_request_id("req-7".to_string(), async {
+            let span = tracing::info_span!("handler");
+            let _span = span.enter();
+            tracing::info!("handled");
+        })
+        .await;
+
+        let event: Value = serde_json::from_slice(buf.0.lock().unwrap().trim_ascii_end()).unwrap();
+        assert_eq!(event["trace.id"], "req-7");
+        assert!(event["span.id"].is_string());
+    }
+
+    #[test]
+    fn test_level_change_after_an_event_fired() {
+        let _lock = log_level::TEST_LOCK.blocking_lock();
+        let initial = log_level::current().directives;
+        let buf = SharedBuf::default();
+        let dispatch = tracing::Dispatch::new(
+            Registry::default().with(EcsLayer::new().with_writer(buf.clone()).with_filter(level_filter())),
+        );
+        let probe = || tracing::debug!(target: "axum_api::interest_probe", "probe");
+        // The interest is rebuilt for the default subscriber, which the global one is outside
+        // of the tests
+        let set = |directives: &str| {
+            let directives = directives.parse().unwrap();
+            tracing::dispatcher::with_default(&dispatch, || log_level::set(directives, None, "admin-1"))
+        };
+
+        set("info");
+        tracing::dispatcher::with_default(&dispatch, probe);
+        assert!(buf.0.lock().unwrap().is_empty());
+
+        set("info,axum_api::interest_probe=debug");
+        tracing::dispatcher::with_default(&dispatch, probe);
+        set(&initial);
+        assert!(!buf.0.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_log_level_of() {
+        assert_eq!(log_level_of(tracing::Level::INFO), log::Level::Info);
+        assert_eq!(log_level_of(tracing::Level::TRACE), log::Level::Trace);
+    }
+}
@@ -0,0 +1,339 @@
+//! Non-blocking writer of the log events
+//!
+//! The loggers hand every formatted event to a bounded buffer, drained by a background thread
+//! which writes it to each configured sink: stderr, a [rotating file](rolling_file) and a
+//! [syslog collector over UDP](syslog). When the buffer is full the loggers either wait for
+//! room or drop the event; dropped events are counted, exposed as the `log_events_dropped_total`
+//! metric and reported in the stream once room is made.
+
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::constants::logging_constants::*;
+use crate::logging::writer::rolling_file::{RollingFile, Rotation};
+use crate::logging::writer::syslog::SyslogSink;
+use crate::metrics::metrics;
+use crate::util::env::env_parse;
+
+pub mod rolling_file;
+pub mod syslog;
+
+/// Behaviour of the loggers when the buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the writer to make room, slowing the caller down
+    Block,
+    /// Discard the event and count it
+    Drop,
+}
+
+/// Destination of the log events
+#[derive(Debug, Clone, PartialEq)]
+pub enum SinkConfig {
+    Stderr,
+    File {
+        path: PathBuf,
+        max_bytes: Option<u64>,
+        rotation: Rotation,
+        max_files: usize,
+    },
+    Syslog { addr: String, app_name: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriterConfig {
+    /// Number of events the buffer holds
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+    pub sinks: Vec<SinkConfig>,
+}
+
+impl WriterConfig {
+    pub fn from_env() -> Self {
+        let overflow = match env::var(LOG_OVERFLOW_ENV).unwrap_or(DEFAULT_LOG_OVERFLOW.to_string()).as_str() {
+            "drop" => OverflowPolicy::Drop,
+            _ => OverflowPolicy::Block,
+        };
+        let sinks = env::var(LOG_SINKS_ENV)
+            .unwrap_or(DEFAULT_LOG_SINKS.to_string())
+            .split(',')
+            .filter_map(|sink| match sink.trim() {
+                "stderr" => Some(SinkConfig::Stderr),
+                "file" => Some(SinkConfig::File {
+                    path: env::var(LOG_FILE_PATH_ENV).unwrap_or(DEFAULT_LOG_FILE_PATH.to_string()).into(),
+                    max_bytes: Some(env_parse(LOG_FILE_MAX_BYTES_ENV, DEFAULT_LOG_FILE_MAX_BYTES))
+                        .filter(|max_bytes| *max_bytes > 0),
+                    rotation: Rotation::from_name(
+                        &env::var(LOG_FILE_ROTATION_ENV).unwrap_or(DEFAULT_LOG_FILE_ROTATION.to_string()),
+                    ),
+                    max_files: env_parse(LOG_FILE_MAX_FILES_ENV, DEFAULT_LOG_FILE_MAX_FILES),
+                }),
+                "syslog" => Some(SinkConfig::Syslog {
+                    addr: env::var(LOG_SYSLOG_ADDR_ENV).unwrap_or(DEFAULT_LOG_SYSLOG_ADDR.to_string()),
+                    app_name: env::var(LOG_SYSLOG_APP_NAME_ENV).unwrap_or(DEFAULT_LOG_SYSLOG_APP_NAME.to_string()),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        WriterConfig {
+            capacity: env_parse(LOG_BUFFER_CAPACITY_ENV, DEFAULT_LOG_BUFFER_CAPACITY).max(1),
+            overflow,
+            sinks,
+        }
+    }
+}
+
+enum Message {
+    Event(Vec<u8>),
+    Shutdown,
+}
+
+/// Handle the loggers write the events to. Each write is one event.
+#[derive(Clone)]
+pub struct NonBlocking {
+    sender: SyncSender<Message>,
+    overflow: OverflowPolicy,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let message = Message::Event(buf.to_vec());
+        let sent = match self.overflow {
+            OverflowPolicy::Block => self.sender.send(message).is_ok(),
+            OverflowPolicy::Drop => self.sender.try_send(message).is_ok(),
+        };
+        if !sent {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            metrics().log_events_dropped.inc();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlocking {
+    type Writer = NonBlocking;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Flushes the buffered events and stops the writer thread when dropped. Must be held until the
+/// application exits.
+pub struct WorkerGuard {
+    sender: SyncSender<Message>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        if self.sender.send(Message::Shutdown).is_ok() {
+            if let Some(worker) = self.worker.take() {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+/// Start the writer thread
+pub fn non_blocking(config: WriterConfig) -> (NonBlocking, WorkerGuard) {
+    let (sender, receiver) = mpsc::sync_channel(config.capacity);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let sinks = open_sinks(&config.sinks);
+
+    let worker = {
+        let dropped = dropped.clone();
+        thread::Builder::new()
+            .name("log-writer".to_string())
+            .spawn(move || run(receiver, sinks, dropped))
+            .expect("the log writer thread should start")
+    };
+    let writer = NonBlocking {
+        sender: sender.clone(),
+        overflow: config.overflow,
+        dropped,
+    };
+    (writer, WorkerGuard { sender, worker: Some(worker) })
+}
+
+enum Sink {
+    Stderr,
+    File(RollingFile),
+    Syslog(SyslogSink),
+}
+
+impl Sink {
+    fn name(&self) -> &'static str {
+        match self {
+            Sink::Stderr => "stderr",
+            Sink::File(_) => "file",
+            Sink::Syslog(_) => "syslog",
+        }
+    }
+
+    fn write_event(&mut self, event: &[u8]) -> io::Result<()> {
+        match self {
+            Sink::Stderr => io::stderr().write_all(event),
+            Sink::File(file) => file.write_event(event, chrono::Utc::now()),
+            Sink::Syslog(syslog) => syslog.send(event),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stderr => io::stderr().flush(),
+            Sink::File(file) => file.flush(),
+            Sink::Syslog(_) => Ok(()),
+        }
+    }
+}
+
+/// Sinks which could be opened, falling back to stderr. Failures are reported on stderr, as the
+/// logger is not usable yet.
+fn open_sinks(configs: &[SinkConfig]) -> Vec<Sink> {
+    let mut sinks: Vec<Sink> = configs
+        .iter()
+        .filter_map(|config| match config {
+            SinkConfig::Stderr => Some(Sink::Stderr),
+            SinkConfig::File { path, max_bytes, rotation, max_files } => Some(Sink::File(
+                RollingFile::new(path.clone(), *max_bytes, *rotation, *max_files),
+            )),
+            SinkConfig::Syslog { addr, app_name } => match SyslogSink::connect(addr, app_name) {
+                Ok(syslog) => Some(Sink::Syslog(syslog)),
+                Err(err) => {
+                    eprintln!("failed to open the syslog sink {}: {}", addr, err);
+                    None
+                }
+            },
+        })
+        .collect();
+    if sinks.is_empty() {
+        sinks.push(Sink::Stderr);
+    }
+    sinks
+}
+
+/// Write the buffered events until shutdown, flushing the sinks whenever the buffer is drained
+fn run(receiver: Receiver<Message>, mut sinks: Vec<Sink>, dropped: Arc<AtomicU64>) {
+    let mut reported = 0;
+    while let Ok(first) = receiver.recv() {
+        let mut shutdown = false;
+        for message in std::iter::once(first).chain(receiver.try_iter()) {
+            match message {
+                Message::Event(event) => write_event(&mut sinks, &event),
+                Message::Shutdown => {
+                    shutdown = true;
+                    break;
+                }
+            }
+        }
+
+        let total = dropped.load(Ordering::Relaxed);
+        if total > reported {
+            write_event(&mut sinks, &dropped_event(total - reported, total));
+            reported = total;
+        }
+        for sink in &mut sinks {
+            if let Err(err) = sink.flush() {
+                eprintln!("failed to flush the {} log sink: {}", sink.name(), err);
+            }
+        }
+        if shutdown {
+            break;
+        }
+    }
+}
+
+fn write_event(sinks: &mut [Sink], event: &[u8]) {
+    for sink in sinks {
+        if let Err(err) = sink.write_event(event) {
+            eprintln!("failed to write to the {} log sink: {}", sink.name(), err);
+        }
+    }
+}
+
+/// ECS event reporting events dropped since the last report
+fn dropped_event(count: u64, total: u64) -> Vec<u8> {
+    let kvs: Vec<(&str, log::kv::Value)> = vec![("log.dropped", log::kv::Value::from(total))];
+    let mut buf = Vec::new();
+    crate::logging::ecs_logger::format(
+        &mut buf,
+        &log::Record::builder()
+            .args(format_args!("{} log events were dropped as the buffer was full", count))
+            .level(log::Level::Warn)
+            .target(module_path!())
+            .module_path_static(Some(module_path!()))
+            .key_values(&kvs)
+            .build(),
+    )
+    .expect("writing to a vector should not fail");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_drop_when_full() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let mut writer = NonBlocking {
+            sender,
+            overflow: OverflowPolicy::Drop,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+
+        let dropped_before = metrics().log_events_dropped.get();
+        writer.write_all(b"first\n").unwrap();
+        writer.write_all(b"second\n").unwrap();
+        assert_eq!(writer.dropped.load(Ordering::Relaxed), 1);
+        assert!(metrics().log_events_dropped.get() > dropped_before);
+        assert!(matches!(receiver.try_recv(), Ok(Message::Event(event)) if event == b"first\n"));
+    }
+
+    #[test]
+    fn test_dropped_event() {
+        let event: Value = serde_json::from_slice(&dropped_event(3, 10)).unwrap();
+        assert_eq!(event["log.level"], "WARN");
+        assert_eq!(event["log.dropped"], 10);
+        assert_eq!(event["message"], "3 log events were dropped as the buffer was full");
+    }
+
+    #[test]
+    fn test_flush_on_shutdown() {
+        let dir = std::env::temp_dir().join(format!("log-writer-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("app.log");
+        let config = WriterConfig {
+            capacity: 16,
+            overflow: OverflowPolicy::Block,
+            sinks: vec![SinkConfig::File {
+                path: path.clone(),
+                max_bytes: None,
+                rotation: Rotation::Never,
+                max_files: 1,
+            }],
+        };
+
+        let (mut writer, guard) = non_blocking(config);
+        writer.write_all(b"{\"message\":\"a\"}\n").unwrap();
+        writer.write_all(b"{\"message\":\"b\"}\n").unwrap();
+        drop(guard);
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"message\":\"a\"}\n{\"message\":\"b\"}\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+//! Log file rotated by size and on schedule
+//!
+//! The current file keeps its configured path. Rotated files are renamed after the time of their
+//! rotation, `axum_api.log.20231121T103044.512`, and only the most recent ones are kept.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+
+/// Schedule of the rotations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl Rotation {
+    /// Rotation named by the configuration, daily when the name is unknown
+    pub fn from_name(name: &str) -> Self {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "never" => Rotation::Never,
+            "hourly" => Rotation::Hourly,
+            _ => Rotation::Daily,
+        }
+    }
+
+    /// Index of the period `time` falls in; a file is rotated when the period changes
+    fn period(self, time: DateTime<Utc>) -> Option<i64> {
+        match self {
+            Rotation::Never => None,
+            Rotation::Hourly => Some(time.timestamp().div_euclid(3600)),
+            Rotation::Daily => Some(time.timestamp().div_euclid(86400)),
+        }
+    }
+}
+
+pub struct RollingFile {
+    path: PathBuf,
+    max_bytes: Option<u64>,
+    rotation: Rotation,
+    max_files: usize,
+    /// Current file, opened on the first write
+    file: Option<BufWriter<File>>,
+    size: u64,
+    period: Option<i64>,
+}
+
+impl RollingFile {
+    pub fn new(path: PathBuf, max_bytes: Option<u64>, rotation: Rotation, max_files: usize) -> Self {
+        RollingFile {
+            path,
+            max_bytes,
+            rotation,
+            max_files,
+            file: None,
+            size: 0,
+            period: None,
+        }
+    }
+
+    /// Append `event`, rotating the file first when it would exceed its size or its period is over
+    pub fn write_event(&mut self, event: &[u8], now: DateTime<Utc>) -> io::Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let too_large = self
+            .max_bytes
+            .is_some_and(|max_bytes| self.size > 0 && self.size + event.len() as u64 > max_bytes);
+        let period_over = self.size > 0 && self.rotation.period(now) != self.period;
+        if too_large || period_over {
+            self.rotate(now)?;
+        }
+
+        let file = self.file.as_mut().expect("the log file is open");
+        file.write_all(event)?;
+        self.size += event.len() as u64;
+        self.period = self.rotation.period(now);
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Open the current file, resuming its size and the period it was last written in
+    fn open(&mut self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let metadata = file.metadata()?;
+        self.size = metadata.len();
+        self.period = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| self.rotation.period(modified.into()));
+        self.file = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+
+        let mut rotated = self.rotated_name(now, 0);
+        let mut attempt = 0;
+        while rotated.exists() {
+            attempt += 1;
+            rotated = self.rotated_name(now, attempt);
+        }
+        fs::rename(&self.path, rotated)?;
+        self.prune()?;
+        self.open()
+    }
+
+    fn rotated_name(&self, now: DateTime<Utc>, attempt: u32) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(now.format(".%Y%m%dT%H%M%S%.3f").to_string());
+        if attempt > 0 {
+            name.push(format!("-{}", attempt));
+        }
+        self.path.with_file_name(name)
+    }
+
+    /// Delete the oldest rotated files beyond `max_files`
+    fn prune(&self) -> io::Result<()> {
+        let Some(name) = self.path.file_name().and_then(|name| name.to_str()) else {
+            return Ok(());
+        };
+        let prefix = format!("{}.", name);
+        let dir = match self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => dir.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+
+        let mut rotated: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_str().is_some_and(|file| file.starts_with(&prefix)))
+            .map(|entry| entry.path())
+            .collect();
+        // Timestamps sort chronologically
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.max_files);
+        for path in &rotated[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rolling-file-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rotated_files(dir: &PathBuf) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name != "app.log")
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotate_by_size_with_retention() {
+        let dir = temp_dir();
+        let mut file = RollingFile::new(dir.join("app.log"), Some(10), Rotation::Never, 2);
+        for (i, time) in ["10:00:01", "10:00:02", "10:00:03", "10:00:04"].iter().enumerate() {
+            let now = at(&format!("2023-11-21T{}Z", time));
+            file.write_event(format!("event-{}\n", i).as_bytes(), now).unwrap();
+        }
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("app.log")).unwrap(), "event-3\n");
+        assert_eq!(
+            rotated_files(&dir),
+            ["app.log.20231121T100003.000", "app.log.20231121T100004.000"]
+        );
+        assert_eq!(fs::read_to_string(dir.join("app.log.20231121T100004.000")).unwrap(), "event-2\n");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_daily() {
+        let dir = temp_dir();
+        let mut file = RollingFile::new(dir.join("app.log"), None, Rotation::Daily, 7);
+        file.write_event(b"monday\n", at("2023-11-20T23:59:59Z")).unwrap();
+        file.write_event(b"monday again\n", at("2023-11-20T23:59:59Z")).unwrap();
+        file.write_event(b"tuesday\n", at("2023-11-21T00:00:00Z")).unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("app.log")).unwrap(), "tuesday\n");
+        assert_eq!(rotated_files(&dir), ["app.log.20231121T000000.000"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotation_from_name() {
+        assert_eq!(Rotation::from_name("Hourly"), Rotation::Hourly);
+        assert_eq!(Rotation::from_name("never"), Rotation::Never);
+        assert_eq!(Rotation::from_name("weekly"), Rotation::Daily);
+    }
+}
@@ -0,0 +1,95 @@
+//! Forwarding of the log events to a syslog collector over UDP
+//!
+//! Every event is sent as one [RFC 5424](https://www.rfc-editor.org/rfc/rfc5424) message of the
+//! `user` facility, whose body is the ECS JSON and whose severity follows its `log.level`.
+
+use std::io;
+use std::net::UdpSocket;
+
+use chrono::{SecondsFormat, Utc};
+use serde::Deserialize;
+
+/// Facility of the messages, `user`
+const FACILITY: u8 = 1;
+
+pub struct SyslogSink {
+    socket: UdpSocket,
+    hostname: String,
+    app_name: String,
+    pid: u32,
+}
+
+impl SyslogSink {
+    pub fn connect(addr: &str, app_name: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(addr)?;
+        Ok(SyslogSink {
+            socket,
+            hostname: std::env::var("HOSTNAME").unwrap_or("-".to_string()),
+            app_name: app_name.to_string(),
+            pid: std::process::id(),
+        })
+    }
+
+    pub fn send(&self, event: &[u8]) -> io::Result<()> {
+        self.socket.send(&self.message(event)).map(|_| ())
+    }
+
+    fn message(&self, event: &[u8]) -> Vec<u8> {
+        let priority = FACILITY * 8 + severity(event);
+        let header = format!(
+            "<{}>1 {} {} {} {} - - ",
+            priority,
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.hostname,
+            self.app_name,
+            self.pid
+        );
+        let mut message = header.into_bytes();
+        message.extend_from_slice(event.strip_suffix(b"\n").unwrap_or(event));
+        message
+    }
+}
+
+/// Syslog severity of an ECS event, from its `log.level`
+fn severity(event: &[u8]) -> u8 {
+    #[derive(Deserialize)]
+    struct Level<'a> {
+        #[serde(rename = "log.level", borrow)]
+        level: Option<&'a str>,
+    }
+
+    match serde_json::from_slice::<Level>(event).ok().and_then(|event| event.level) {
+        Some("ERROR") => 3,
+        Some("WARN") => 4,
+        Some("INFO") => 6,
+        Some("DEBUG") | Some("TRACE") => 7,
+        _ => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity() {
+        assert_eq!(severity(br#"{"log.level":"ERROR","message":"boom"}"#), 3);
+        assert_eq!(severity(br#"{"message":"a","log.level":"DEBUG"}"#), 7);
+        assert_eq!(severity(b"not json"), 5);
+    }
+
+    #[test]
+    fn test_send() {
+        let collector = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = SyslogSink::connect(&collector.local_addr().unwrap().to_string(), "axum_api").unwrap();
+        sink.send(b"{\"log.level\":\"WARN\",\"message\":\"slow\"}\n").unwrap();
+
+        let mut buf = [0; 1024];
+        let len = collector.recv(&mut buf).unwrap();
+        let message = std::str::from_utf8(&buf[..len]).unwrap();
+        assert!(message.starts_with("<12>1 "), "{}", message);
+        assert!(message.contains(&format!(" axum_api {} - - ", std::process::id())));
+        assert!(message.ends_with("{\"log.level\":\"WARN\",\"message\":\"slow\"}"));
+    }
+}
@@ -1,4 +1,9 @@
+mod archive;
+mod build_info;
+mod extractor;
+mod health;
 mod handler;
+mod i18n;
 mod util;
 mod authentication;
 mod database;
@@ -7,74 +12,46 @@ mod routes;
 mod response;
 mod constants;
 mod logging;
+mod metrics;
 mod s3_client;
+mod state;
+mod upload;
+mod variants;
 
-use axum::{
-    routing::{get, post},
-    http::StatusCode,
-    response::IntoResponse,
-    Json, Router,
-};
-use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
-use crate::handler::*;
-use log::{debug, error, info};
-use std::env;
-use ecs_logger::extra_fields;
 
+use log::{error, info};
 
+use crate::state::AppState;
 
 #[tokio::main]
 async fn main() {
-    env::set_var("RUST_LOG", "info");
-
-    ecs_logger::init();
+    build_info::mark_started();
+    let _log_guard = logging::ecs_logger::init();
 
     // extra_fields::set_extra_fields(MyExtraFields {
     //     my_field: "my_value".to_string(),
     // }).unwrap();
 
+    let mut state = AppState::from_env();
+    match database::run_migrations(&state.db_pool).await {
+        Ok(()) => match database::applied_migration_version(&state.db_pool).await {
+            Ok(version) => state.migration_version = version,
+            Err(err) => error!("failed to read the applied migration version: {}", err),
+        },
+        Err(err) => error!("failed to run database migrations: {}", err),
+    }
+    upload::sweeper::spawn_pending_upload_sweeper(state.db_pool.clone(), state.s3_client.clone());
+
     // build our application with a route
-    let app = Router::new()
-        .route("/", get(version_handler::get_version));
-        // // `POST /users` goes to `create_user`
-        // .route("/users", post(create_user));
+    let app = routes::create_router(state);
 
     // run our app with hyper `axum::Server` is a re-export of `hyper::Server`
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     info!("listening on {}", addr);
     axum::Server::bind(&addr)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }
 
-
-async fn create_user(
-    // this argument tells axum to parse the request body
-    // as JSON into a `CreateUser` type
-    Json(payload): Json<CreateUser>,
-) -> (StatusCode, Json<User>) {
-    // insert your application logic here
-    let user = User {
-        id: 1337,
-        username: payload.username,
-    };
-
-    // this will be converted into a JSON response
-    // with a status code of `201 Created`
-    (StatusCode::CREATED, Json(user))
-}
-
-// the input to our `create_user` handler
-#[derive(Deserialize)]
-struct CreateUser {
-    username: String,
-}
-
-// the output to our `create_user` handler
-#[derive(Serialize)]
-struct User {
-    id: u64,
-    username: String,
-}
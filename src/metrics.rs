@@ -0,0 +1,192 @@
+//! Prometheus metrics of the API
+//!
+//! The metrics are registered once in a registry of their own, and exposed in the Prometheus
+//! text format by `GET /metrics`. Histograms measure seconds, and every label takes a bounded
+//! set of values: HTTP requests are labelled by their route template, not their path.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::constants::metrics_constants::*;
+use crate::database::DbPool;
+
+static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    /// By `method`, `route` and `status`
+    pub http_requests: IntCounterVec,
+    /// By `method`, `route` and `status`
+    pub http_request_duration: HistogramVec,
+    pub http_requests_in_flight: IntGauge,
+    /// Connections of the pool, by `state`: `idle` or `in_use`
+    pub db_pool_connections: IntGaugeVec,
+    pub db_pool_max_connections: IntGauge,
+    /// By `outcome`: `success` or `error`
+    pub db_query_duration: HistogramVec,
+    /// By `operation` and `outcome`
+    pub s3_operation_duration: HistogramVec,
+    /// By `operation`
+    pub s3_operation_errors: IntCounterVec,
+    /// By `reason`, such as `expired` or `invalid_signature`
+    pub jwt_validation_failures: IntCounterVec,
+    /// Log events discarded as the buffer of the log writer was full
+    pub log_events_dropped: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let metrics = Metrics {
+            http_requests: IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests handled"),
+                &["method", "route", "status"],
+            )
+            .unwrap(),
+            http_request_duration: HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "Time to build the HTTP responses")
+                    .buckets(HTTP_DURATION_BUCKETS.to_vec()),
+                &["method", "route", "status"],
+            )
+            .unwrap(),
+            http_requests_in_flight: IntGauge::new("http_requests_in_flight", "HTTP requests being handled").unwrap(),
+            db_pool_connections: IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Connections of the database pool"),
+                &["state"],
+            )
+            .unwrap(),
+            db_pool_max_connections: IntGauge::new(
+                "db_pool_max_connections",
+                "Maximum number of connections of the database pool",
+            )
+            .unwrap(),
+            db_query_duration: HistogramVec::new(
+                HistogramOpts::new("db_query_duration_seconds", "Time to run the database queries")
+                    .buckets(DB_DURATION_BUCKETS.to_vec()),
+                &["outcome"],
+            )
+            .unwrap(),
+            s3_operation_duration: HistogramVec::new(
+                HistogramOpts::new("s3_operation_duration_seconds", "Time to run the object store operations")
+                    .buckets(S3_DURATION_BUCKETS.to_vec()),
+                &["operation", "outcome"],
+            )
+            .unwrap(),
+            s3_operation_errors: IntCounterVec::new(
+                Opts::new("s3_operation_errors_total", "Object store operations which failed"),
+                &["operation"],
+            )
+            .unwrap(),
+            jwt_validation_failures: IntCounterVec::new(
+                Opts::new("jwt_validation_failures_total", "Bearer tokens rejected"),
+                &["reason"],
+            )
+            .unwrap(),
+            log_events_dropped: IntCounter::new(
+                "log_events_dropped_total",
+                "Log events dropped as the log buffer was full",
+            )
+            .unwrap(),
+            registry,
+        };
+
+        metrics.registry.register(Box::new(metrics.http_requests.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.http_request_duration.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.http_requests_in_flight.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.db_pool_connections.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.db_pool_max_connections.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.db_query_duration.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.s3_operation_duration.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.s3_operation_errors.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.jwt_validation_failures.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.log_events_dropped.clone())).unwrap();
+        metrics
+    }
+
+    /// Record the state of the database pool, which is sampled when scraped
+    pub fn observe_db_pool(&self, pool: &DbPool) {
+        let state = pool.state();
+        let idle = i64::from(state.idle_connections);
+        self.db_pool_connections.with_label_values(&["idle"]).set(idle);
+        self.db_pool_connections
+            .with_label_values(&["in_use"])
+            .set(i64::from(state.connections) - idle);
+        self.db_pool_max_connections.set(i64::from(pool.max_size()));
+    }
+
+    pub fn observe_db_query(&self, duration: Duration, success: bool) {
+        self.db_query_duration
+            .with_label_values(&[outcome(success)])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_s3_operation(&self, operation: &str, duration: Duration, success: bool) {
+        self.s3_operation_duration
+            .with_label_values(&[operation, outcome(success)])
+            .observe(duration.as_secs_f64());
+        if !success {
+            self.s3_operation_errors.with_label_values(&[operation]).inc();
+        }
+    }
+
+    /// Metrics in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("encoding the metrics should not fail");
+        String::from_utf8(buf).expect("the metrics are UTF-8")
+    }
+}
+
+fn outcome(success: bool) -> &'static str {
+    if success {
+        "success"
+    } else {
+        "error"
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Run the object store `operation`, recording its latency and whether it failed
+pub async fn observe_s3<T, E>(operation: &str, future: impl Future<Output = Result<T, E>>) -> Result<T, E> {
+    let start = Instant::now();
+    let result = future.await;
+    metrics().observe_s3_operation(operation, start.elapsed(), result.is_ok());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_observe_s3() {
+        let ok: Result<(), ()> = observe_s3("test_put_object", async { Ok(()) }).await;
+        let err: Result<(), ()> = observe_s3("test_put_object", async { Err(()) }).await;
+        assert!(ok.is_ok() && err.is_err());
+
+        let histogram = metrics().s3_operation_duration.with_label_values(&["test_put_object", "success"]);
+        assert_eq!(histogram.get_sample_count(), 1);
+        assert_eq!(metrics().s3_operation_errors.with_label_values(&["test_put_object"]).get(), 1);
+    }
+
+    #[test]
+    fn test_encode() {
+        metrics().jwt_validation_failures.with_label_values(&["expired"]).inc();
+
+        let encoded = metrics().encode();
+        assert!(encoded.contains("# TYPE jwt_validation_failures_total counter"));
+        assert!(encoded.contains("jwt_validation_failures_total{reason=\"expired\"}"));
+        assert!(encoded.contains("# TYPE http_requests_in_flight gauge"));
+        assert!(encoded.contains("# TYPE log_events_dropped_total counter"));
+    }
+}
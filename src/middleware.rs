@@ -1 +1,7 @@
-mod auth_middleware;
\ No newline at end of file
+pub mod access_log_middleware;
+pub mod auth_middleware;
+pub mod format_middleware;
+pub mod locale_middleware;
+pub mod metrics_middleware;
+pub mod problem_middleware;
+pub mod request_id_middleware;
\ No newline at end of file
@@ -0,0 +1,171 @@
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use axum::{
+    body::HttpBody,
+    extract::ConnectInfo,
+    http::{header, HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use log::{kv, Level, Record};
+
+use crate::constants::logging_constants::ACCESS_LOG_TARGET;
+use crate::middleware::auth_middleware::AuthenticatedUser;
+
+/// Log one ECS event per request once its response is built. Must be layered inside
+/// [`propagate_request_id`](crate::middleware::request_id_middleware::propagate_request_id) so
+/// the event carries the ID of the request.
+pub async fn access_log<B>(req: Request<B>, next: Next<B>) -> Response {
+    if !log::log_enabled!(target: ACCESS_LOG_TARGET, Level::Info) {
+        return next.run(req).await;
+    }
+
+    let start = Instant::now();
+    let mut event = AccessEvent {
+        method: req.method().to_string(),
+        path: req.uri().path().to_string(),
+        query: req.uri().query().map(str::to_string),
+        client_ip: req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string()),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        request_bytes: content_length(req.headers()),
+        ..AccessEvent::default()
+    };
+
+    let response = next.run(req).await;
+    event.status_code = response.status().as_u16();
+    event.response_bytes = response
+        .body()
+        .size_hint()
+        .exact()
+        .or_else(|| content_length(response.headers()));
+    event.user_id = response.extensions().get::<AuthenticatedUser>().map(|user| user.id.clone());
+    event.duration_ns = u64::try_from(start.elapsed().as_nanos()).unwrap_or(u64::MAX);
+
+    event.log();
+    response
+}
+
+/// Request and response attributes reported by the access log, in ECS field names
+#[derive(Debug, Clone, Default, PartialEq)]
+struct AccessEvent {
+    method: String,
+    path: String,
+    query: Option<String>,
+    status_code: u16,
+    /// Body sizes are only known when announced by `Content-Length` or the body itself
+    request_bytes: Option<u64>,
+    response_bytes: Option<u64>,
+    duration_ns: u64,
+    client_ip: Option<String>,
+    user_agent: Option<String>,
+    /// `sub` of the JWT of authenticated requests
+    user_id: Option<String>,
+}
+
+impl AccessEvent {
+    fn fields(&self) -> Vec<(&'static str, kv::Value<'_>)> {
+        let mut fields = vec![
+            ("http.request.method", kv::Value::from(self.method.as_str())),
+            ("url.path", kv::Value::from(self.path.as_str())),
+            ("http.response.status_code", kv::Value::from(self.status_code)),
+            ("event.duration", kv::Value::from(self.duration_ns)),
+        ];
+        let optional = [
+            ("url.query", self.query.as_deref().map(kv::Value::from)),
+            ("http.request.body.bytes", self.request_bytes.map(kv::Value::from)),
+            ("http.response.body.bytes", self.response_bytes.map(kv::Value::from)),
+            ("client.ip", self.client_ip.as_deref().map(kv::Value::from)),
+            ("user_agent.original", self.user_agent.as_deref().map(kv::Value::from)),
+            ("user.id", self.user_id.as_deref().map(kv::Value::from)),
+        ];
+        fields.extend(optional.into_iter().filter_map(|(key, value)| Some((key, value?))));
+        fields
+    }
+
+    fn log(&self) {
+        let fields = self.fields();
+        log::logger().log(
+            &Record::builder()
+                .args(format_args!("{} {} {}", self.method, self.path, self.status_code))
+                .level(Level::Info)
+                .target(ACCESS_LOG_TARGET)
+                .module_path_static(Some(module_path!()))
+                .file_static(Some(file!()))
+                .line(Some(line!()))
+                .key_values(&fields)
+                .build(),
+        );
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use serde_json::Value;
+
+    use crate::logging::ecs_logger::format;
+
+    fn format_event(event: &AccessEvent) -> Value {
+        let fields = event.fields();
+        let record = Record::builder()
+            .args(format_args!("access"))
+            .level(Level::Info)
+            .target(ACCESS_LOG_TARGET)
+            .key_values(&fields)
+            .build();
+        let mut buf = Vec::new();
+        format(&mut buf, &record).unwrap();
+        serde_json::from_slice(&buf).unwrap()
+    }
+
+    #[test]
+    fn test_content_length() {
+        let headers = HeaderMap::from_iter([(header::CONTENT_LENGTH, HeaderValue::from_static("512"))]);
+        assert_eq!(content_length(&headers), Some(512));
+        assert_eq!(content_length(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_event_fields() {
+        let event = AccessEvent {
+            method: "GET".to_string(),
+            path: "/files/42".to_string(),
+            query: Some("download=1".to_string()),
+            status_code: 200,
+            request_bytes: None,
+            response_bytes: Some(1024),
+            duration_ns: 1_500_000,
+            client_ip: Some("10.0.0.7".to_string()),
+            user_agent: Some("curl/8.4.0".to_string()),
+            user_id: Some("user-1".to_string()),
+        };
+
+        let logged = format_event(&event);
+        assert_eq!(logged["http.request.method"], "GET");
+        assert_eq!(logged["url.path"], "/files/42");
+        assert_eq!(logged["url.query"], "download=1");
+        assert_eq!(logged["http.response.status_code"], 200);
+        assert_eq!(logged["http.response.body.bytes"], 1024);
+        assert_eq!(logged["event.duration"], 1_500_000);
+        assert_eq!(logged["client.ip"], "10.0.0.7");
+        assert_eq!(logged["user_agent.original"], "curl/8.4.0");
+        assert_eq!(logged["user.id"], "user-1");
+        assert!(logged.get("http.request.body.bytes").is_none());
+    }
+}
@@ -1,8 +1,76 @@
 use axum::{
-    Router,
-    http::{self, Request},
-    routing::get,
+    http::{header, Request},
     response::Response,
-    middleware::{self, Next},
+    middleware::Next,
 };
+use log::debug;
 
+use crate::authentication::jwt::{decode_jwt, failure_reason, Claims};
+use crate::authentication::role::Role;
+use crate::constants::jwt_constants::{BEARER, JWT_AUDIENCE};
+use crate::i18n::supported_locale;
+use crate::logging::error_fields::with_error_fields;
+use crate::metrics::metrics;
+use crate::middleware::locale_middleware::localized;
+use crate::response::api_error::ApiError;
+
+/// User authenticated by [`require_auth`], recorded in the response extensions for the
+/// layers which report on the request, such as the access log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedUser {
+    pub id: String,
+}
+
+/// Require a valid bearer JWT and expose its [`Claims`]
+/// to the handlers through the request extensions. The locale preferred by the user, if any,
+/// overrides the one negotiated from `Accept-Language`.
+pub async fn require_auth<B>(
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(BEARER));
+
+    let claims = match token.map(|token| decode_jwt(token, vec![JWT_AUDIENCE.to_string()])) {
+        Some(Ok(claims)) => claims,
+        Some(Err(err)) => {
+            with_error_fields(&err, || debug!("rejected bearer token: {}", err));
+            metrics().jwt_validation_failures.with_label_values(&[failure_reason(&err)]).inc();
+            return Err(ApiError::Unauthorized);
+        }
+        None => {
+            metrics().jwt_validation_failures.with_label_values(&["missing"]).inc();
+            return Err(ApiError::Unauthorized);
+        }
+    };
+
+    let locale = claims.locale.as_deref().and_then(supported_locale);
+    let user = AuthenticatedUser { id: claims.sub.clone() };
+    req.extensions_mut().insert(claims);
+    let mut response = match locale {
+        Some(locale) => localized(locale, req, next).await,
+        None => next.run(req).await,
+    };
+    response.extensions_mut().insert(user);
+    Ok(response)
+}
+
+/// Only let requests through whose token carries the admin role. Must be layered inside
+/// [`require_auth`].
+pub async fn require_admin<B>(
+    req: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    let is_admin = req
+        .extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| Role::from_str(&claims.role) == Role::Admin);
+    if !is_admin {
+        return Err(ApiError::forbidden("the admin role is required"));
+    }
+
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,15 @@
+use axum::{
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::response::format::{with_response_format, BodyFormat};
+
+/// Render the responses of the request in the encoding preferred by its `Accept` header
+pub async fn negotiate_format<B>(req: Request<B>, next: Next<B>) -> Response {
+    let format = BodyFormat::from_accept(req.headers());
+    let mut response = with_response_format(format, next.run(req)).await;
+    response.headers_mut().append(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
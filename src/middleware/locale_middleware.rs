@@ -0,0 +1,34 @@
+use axum::{
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::i18n::{current_locale, negotiate_locale, with_locale};
+
+/// Localize the messages of the request in the language preferred by its `Accept-Language`
+/// header. [`require_auth`](crate::middleware::auth_middleware::require_auth) narrows it down
+/// to the preference of the user, when their token carries one.
+pub async fn negotiate_language<B>(req: Request<B>, next: Next<B>) -> Response {
+    let locale = negotiate_locale(req.headers());
+    let mut response = with_locale(locale, next.run(req)).await;
+
+    let headers = response.headers_mut();
+    if !headers.contains_key(header::CONTENT_LANGUAGE) {
+        headers.insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale));
+    }
+    headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    response
+}
+
+/// Run the rest of the request in `locale`, reporting it in `Content-Language`
+pub async fn localized<B>(locale: &'static str, req: Request<B>, next: Next<B>) -> Response {
+    if locale == current_locale() {
+        return next.run(req).await;
+    }
+    let mut response = with_locale(locale, next.run(req)).await;
+    response
+        .headers_mut()
+        .insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale));
+    response
+}
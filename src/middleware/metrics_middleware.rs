@@ -0,0 +1,77 @@
+use std::time::Instant;
+
+use axum::{
+    extract::MatchedPath,
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+
+use crate::constants::metrics_constants::UNMATCHED_ROUTE;
+use crate::metrics::metrics;
+
+/// Count the requests and measure their latency, by method, route template and status
+pub async fn track_metrics<B>(req: Request<B>, next: Next<B>) -> Response {
+    let start = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE.to_string(), |path| path.as_str().to_string());
+
+    let response = {
+        let _in_flight = InFlight::start();
+        next.run(req).await
+    };
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    metrics().http_requests.with_label_values(&labels).inc();
+    metrics()
+        .http_request_duration
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+    response
+}
+
+/// Counts a request in flight until dropped, so cancelled requests are not left counted
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        metrics().http_requests_in_flight.inc();
+        InFlight
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        metrics().http_requests_in_flight.dec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::StatusCode, middleware, routing::get, Router};
+    use tower::Service;
+
+    #[tokio::test]
+    async fn test_track_metrics() {
+        let mut router: Router = Router::new()
+            .route("/metrics-test/:file_id", get(|| async { StatusCode::NO_CONTENT }))
+            .layer(middleware::from_fn(track_metrics));
+
+        for uri in ["/metrics-test/1", "/metrics-test/2", "/metrics-test-missing"] {
+            router.call(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+        }
+
+        let requests = &metrics().http_requests;
+        assert_eq!(requests.with_label_values(&["GET", "/metrics-test/:file_id", "204"]).get(), 2);
+        let duration = metrics()
+            .http_request_duration
+            .with_label_values(&["GET", "/metrics-test/:file_id", "204"]);
+        assert_eq!(duration.get_sample_count(), 2);
+        assert!(requests.with_label_values(&["GET", UNMATCHED_ROUTE, "404"]).get() >= 1);
+    }
+}
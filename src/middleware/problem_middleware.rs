@@ -0,0 +1,84 @@
+use axum::{
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::response::problem::{accepts_problem_json, ProblemDetails, PROBLEM_JSON};
+
+/// Render errors as `application/problem+json` for the clients asking for it.
+///
+/// Only responses built from an [`ApiError`](crate::response::api_error::ApiError) are
+/// rewritten; their status and headers are kept.
+pub async fn problem_details<B>(req: Request<B>, next: Next<B>) -> Response {
+    let wants_problem = accepts_problem_json(req.headers());
+    let instance = req.uri().path().to_string();
+
+    let mut response = next.run(req).await;
+    if !wants_problem {
+        return response;
+    }
+    let Some(mut problem) = response.extensions_mut().remove::<ProblemDetails>() else {
+        return response;
+    };
+    problem.instance = Some(instance);
+
+    let (mut parts, _) = response.into_parts();
+    let mut problem_response = Json(problem).into_response();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+    *problem_response.status_mut() = parts.status;
+    *problem_response.headers_mut() = parts.headers;
+    problem_response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, body::HttpBody, http::StatusCode, middleware, routing::get, Router};
+    use tower::Service;
+
+    use crate::response::api_error::ApiError;
+
+    async fn call(accept: &str) -> (StatusCode, Option<String>, serde_json::Value) {
+        let mut router: Router = Router::new()
+            .route("/files/:file_id", get(|| async { Err::<(), _>(ApiError::not_found()) }))
+            .layer(middleware::from_fn(problem_details));
+        let req = Request::builder()
+            .uri("/files/42")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+
+        let mut response = router.call(req).await.unwrap();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let mut body = Vec::new();
+        while let Some(chunk) = response.body_mut().data().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        (response.status(), content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_problem_details() {
+        let (status, content_type, body) = call("application/problem+json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type.as_deref(), Some(PROBLEM_JSON));
+        assert_eq!(body["type"], "/meta/status-codes#not_found");
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["instance"], "/files/42");
+    }
+
+    #[tokio::test]
+    async fn test_generic_response_by_default() {
+        let (status, content_type, body) = call("application/json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(content_type.as_deref(), Some("application/json"));
+        assert_eq!(body["status_code"], 101);
+        assert!(body.get("type").is_none());
+    }
+}
@@ -0,0 +1,78 @@
+use axum::{
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::constants::logging_constants::{MAX_REQUEST_ID_LEN, REQUEST_ID_HEADER};
+use crate::logging::request_context::with_request_id;
+
+/// Identify the request by its `X-Request-Id` header, or a new ID when it is missing or
+/// invalid. The ID is added to the log events of the request and echoed in the response.
+pub async fn propagate_request_id<B>(req: Request<B>, next: Next<B>) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| is_valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let header_value = HeaderValue::from_str(&request_id).unwrap();
+    let mut response = with_request_id(request_id, next.run(req)).await;
+    response.headers_mut().insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+/// Only IDs made of a safe charset are trusted, so they cannot forge log content
+fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, body::HttpBody, middleware, routing::get, Router};
+    use tower::Service;
+
+    use crate::logging::request_context::current_request_id;
+
+    async fn call(request_id: Option<&str>) -> (String, String) {
+        let mut router: Router = Router::new()
+            .route("/", get(|| async { current_request_id().unwrap_or_default() }))
+            .layer(middleware::from_fn(propagate_request_id));
+        let mut req = Request::builder().uri("/");
+        if let Some(request_id) = request_id {
+            req = req.header(REQUEST_ID_HEADER, request_id);
+        }
+
+        let mut response = router.call(req.body(Body::empty()).unwrap()).await.unwrap();
+        let header = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.body_mut().data().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        (header, String::from_utf8(body).unwrap())
+    }
+
+    #[test]
+    fn test_is_valid_request_id() {
+        assert!(is_valid_request_id("7f4c0a6e-1b2d-4c3e-9f00-1a2b3c4d5e6f"));
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("id with spaces"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn test_propagate_request_id() {
+        assert_eq!(call(Some("abc-123")).await, ("abc-123".to_string(), "abc-123".to_string()));
+
+        let (header, body) = call(Some("bad id\"}")).await;
+        assert_eq!(header, body);
+        assert!(uuid::Uuid::parse_str(&header).is_ok());
+    }
+}
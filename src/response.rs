@@ -1 +1,5 @@
-pub mod api_response;
\ No newline at end of file
+pub mod api_error;
+pub mod api_response;
+pub mod format;
+pub mod problem;
+pub mod status_code;
\ No newline at end of file
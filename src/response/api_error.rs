@@ -0,0 +1,251 @@
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::{debug, error};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::database::DbError;
+use crate::i18n::status_message;
+use crate::logging::error_fields::with_error_fields;
+use crate::response::api_response::*;
+use crate::response::problem::ProblemDetails;
+
+/// A field of the request which failed a validation rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// Path of the field, such as `owners[2].email`
+    pub field: String,
+    /// Machine-readable name of the failed rule, such as `length` or `email`
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Parameters of the rule, such as its `min` and `max`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub params: Map<String, Value>,
+}
+
+/// Payload of the [`GenericResponse`] rendered for an [`ApiError`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ErrorData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+/// Error returned by the handlers.
+///
+/// Client errors render their reason in the `data` of the [`GenericResponse`]. Upstream and
+/// internal errors are logged with their full cause chain and rendered without any detail.
+/// The response also carries the equivalent [`ProblemDetails`] for clients negotiating them.
+#[derive(Error, Debug)]
+pub enum ApiError {
+    /// The request is invalid. `status_code` narrows down the reason for the clients.
+    #[error("{reason}")]
+    Validation { status_code: u16, reason: String },
+
+    /// The request carries no valid credentials.
+    #[error("authentication is required")]
+    Unauthorized,
+
+    /// Fields of the request failed their validation rules.
+    #[error("{} field(s) of the request are invalid", .0.len())]
+    InvalidFields(Vec<FieldError>),
+
+    /// The caller is not allowed to perform the request.
+    #[error("{reason}")]
+    Forbidden { status_code: u16, reason: String },
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    /// The body is in a format the endpoint does not accept.
+    #[error("{0}")]
+    UnsupportedMediaType(String),
+
+    #[error("the request exceeds the maximum size of {limit} bytes")]
+    PayloadTooLarge { limit: u64 },
+
+    /// None of the requested ranges overlap the `total_len` bytes of the representation.
+    #[error("the requested range is not satisfiable")]
+    RangeNotSatisfiable { total_len: u64 },
+
+    /// Reserved for rate limiting, which no route applies yet.
+    #[allow(dead_code)]
+    #[error("too many requests")]
+    RateLimited { retry_after_secs: Option<u64> },
+
+    /// A dependency such as the object store failed.
+    #[error("upstream service failed: {0:#}")]
+    Upstream(anyhow::Error),
+
+    #[error("internal error: {0:#}")]
+    Internal(anyhow::Error),
+}
+
+impl ApiError {
+    pub fn validation(reason: impl Into<String>) -> Self {
+        ApiError::Validation {
+            status_code: STATUS_BAD_REQUEST,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn forbidden(reason: impl Into<String>) -> Self {
+        ApiError::Forbidden {
+            status_code: STATUS_FORBIDDEN,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        ApiError::NotFound("the resource does not exist".to_string())
+    }
+
+    /// HTTP status and application status code of the error
+    pub fn status(&self) -> (StatusCode, u16) {
+        match self {
+            ApiError::Validation { status_code, .. } => (StatusCode::BAD_REQUEST, *status_code),
+            ApiError::InvalidFields(_) => (StatusCode::BAD_REQUEST, STATUS_VALIDATION_FAILED),
+            ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, STATUS_UNAUTHORIZED),
+            ApiError::Forbidden { status_code, .. } => (StatusCode::FORBIDDEN, *status_code),
+            ApiError::NotFound(_) => (StatusCode::NOT_FOUND, STATUS_NOT_FOUND),
+            ApiError::Conflict(_) => (StatusCode::CONFLICT, STATUS_CONFLICT),
+            ApiError::UnsupportedMediaType(_) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, STATUS_UNSUPPORTED_MEDIA_TYPE),
+            ApiError::PayloadTooLarge { .. } => (StatusCode::PAYLOAD_TOO_LARGE, STATUS_PAYLOAD_TOO_LARGE),
+            ApiError::RangeNotSatisfiable { .. } => {
+                (StatusCode::RANGE_NOT_SATISFIABLE, STATUS_RANGE_NOT_SATISFIABLE)
+            }
+            ApiError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, STATUS_RATE_LIMITED),
+            ApiError::Upstream(_) => (StatusCode::BAD_GATEWAY, STATUS_UPSTREAM_ERROR),
+            ApiError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, STATUS_INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    /// Parameters interpolated into the localized message of the error
+    fn message_params(&self) -> Vec<(&'static str, String)> {
+        match self {
+            ApiError::InvalidFields(fields) => vec![("count", fields.len().to_string())],
+            ApiError::PayloadTooLarge { limit } => vec![("limit", limit.to_string())],
+            ApiError::RangeNotSatisfiable { total_len } => vec![("total_len", total_len.to_string())],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Detail exposed to the client, `None` for errors whose detail must stay internal
+    fn public_reason(&self) -> Option<String> {
+        match self {
+            ApiError::Upstream(_) | ApiError::Internal(_) | ApiError::Unauthorized => None,
+            err => Some(err.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, status_code) = self.status();
+        match &self {
+            ApiError::Upstream(err) | ApiError::Internal(err) => with_error_fields(err, || error!("{}", self)),
+            err => debug!("request rejected: {}", err),
+        }
+
+        let reason = self.public_reason();
+        let mut problem = ProblemDetails::new(status, status_code, reason.clone());
+        let message = status_message(status_code, &self.message_params());
+        let mut body: GenericResponse<ErrorData> = GenericResponse::error(status_code, message);
+        body.data.reason = reason;
+        if let ApiError::InvalidFields(fields) = &self {
+            body.data.fields = fields.clone();
+            problem.errors = fields.clone();
+        }
+
+        let mut response = (status, body).into_response();
+        response.extensions_mut().insert(problem);
+        match self {
+            ApiError::RangeNotSatisfiable { total_len } => {
+                response.headers_mut().insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&format!("bytes */{}", total_len)).unwrap(),
+                );
+            }
+            ApiError::RateLimited { retry_after_secs: Some(secs) } => {
+                response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+            }
+            _ => {}
+        }
+        response
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::Query(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                ApiError::Conflict("the resource already exists".to_string())
+            }
+            err => ApiError::Internal(err.into()),
+        }
+    }
+}
+
+impl From<SdkError<HeadObjectError>> for ApiError {
+    fn from(err: SdkError<HeadObjectError>) -> Self {
+        match err {
+            SdkError::ServiceError(ref service_err) if service_err.err().is_not_found() => ApiError::not_found(),
+            err => ApiError::Upstream(err.into()),
+        }
+    }
+}
+
+impl From<SdkError<GetObjectError>> for ApiError {
+    fn from(err: SdkError<GetObjectError>) -> Self {
+        match err {
+            SdkError::ServiceError(ref service_err) if service_err.err().is_no_such_key() => ApiError::not_found(),
+            err => ApiError::Upstream(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        assert_eq!(
+            ApiError::validation("bad").status(),
+            (StatusCode::BAD_REQUEST, STATUS_BAD_REQUEST)
+        );
+        assert_eq!(ApiError::not_found().status(), (StatusCode::NOT_FOUND, STATUS_NOT_FOUND));
+        assert_eq!(
+            ApiError::Internal(anyhow::anyhow!("boom")).status(),
+            (StatusCode::INTERNAL_SERVER_ERROR, STATUS_INTERNAL_SERVER_ERROR)
+        );
+    }
+
+    #[test]
+    fn test_public_reason_is_sanitized() {
+        assert_eq!(ApiError::validation("bad size").public_reason(), Some("bad size".to_string()));
+        assert_eq!(ApiError::Internal(anyhow::anyhow!("password=hunter2")).public_reason(), None);
+        assert_eq!(ApiError::Upstream(anyhow::anyhow!("s3 timeout")).public_reason(), None);
+    }
+
+    #[test]
+    fn test_into_response_headers() {
+        let response = ApiError::RangeNotSatisfiable { total_len: 42 }.into_response();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */42");
+
+        let response = ApiError::RateLimited { retry_after_secs: Some(30) }.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+    }
+}
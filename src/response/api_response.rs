@@ -1,42 +1,91 @@
-use std::any::Any;
-use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::Json;
-use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
-use serde_json::{Value, Map, Number};
-use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 
-pub const STATUS_NO_ERROR: i8 = 0;
-pub const STATUS_BAD_REQUEST: i8 = 1;
-pub const STATUS_REQUEST_TIMEOUT_ERROR: i8 = 2;
-pub const STATUS_INTERNAL_SERVER_ERROR: i8 = 3;
-
-
-pub const STATUS_NO_ERROR_STR: &str = "OK";
-pub const STATUS_BAD_REQUEST_STR: &str = "Bad Request";
-pub const STATUS_REQUEST_TIMEOUT_ERROR_STR: &str = "Request Timeout";
-pub const STATUS_INTERNAL_SERVER_ERROR_STR: &str = "Internal Server Error";
-
-
-pub static STATUS_MAPPER: Lazy<HashMap<i8, &str>> = Lazy::new(|| HashMap::from(
-    [
-        (STATUS_NO_ERROR, STATUS_NO_ERROR_STR),
-        (STATUS_BAD_REQUEST, STATUS_BAD_REQUEST_STR),
-        (STATUS_REQUEST_TIMEOUT_ERROR, STATUS_REQUEST_TIMEOUT_ERROR_STR),
-        (STATUS_INTERNAL_SERVER_ERROR, STATUS_INTERNAL_SERVER_ERROR_STR),
-    ],
-));
+use crate::i18n::status_message;
+use crate::logging::request_context::current_request_id;
+use crate::response::format::render;
+pub use crate::response::status_code::*;
 
 /// Define the common response for all api call
-#[derive(Debug, Serialize)]
-pub struct GenericResponse<'a> {
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenericResponse<T> {
     /// The status string for the response
-    pub status: &'a str,
+    pub status: String,
     /// The status code for the response
-    pub status_code: i8,
+    pub status_code: u16,
     /// the message string for the response
-    pub message: &'a str,
-    /// the optional data map for the response
-    pub data: HashMap<&'a str, Value>,
+    pub message: String,
+    /// the data payload for the response
+    pub data: T,
+    /// the ID of the request, as in the `X-Request-Id` response header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Payload of responses which carry no data, serialized as an empty object
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Empty {}
+
+impl<T> GenericResponse<T> {
+    pub fn new(status_code: u16, message: impl Into<String>, data: T) -> Self {
+        GenericResponse {
+            status: status_str(status_code).to_string(),
+            status_code,
+            message: message.into(),
+            data,
+            request_id: current_request_id(),
+        }
+    }
+
+    /// Successful response carrying `data`, with the message localized for the request
+    pub fn ok(data: T) -> Self {
+        GenericResponse::new(STATUS_NO_ERROR, status_message(STATUS_NO_ERROR, &[]), data)
+    }
+}
+
+impl<T: Default> GenericResponse<T> {
+    /// Error response for `status_code`, with an empty payload
+    pub fn error(status_code: u16, message: impl Into<String>) -> Self {
+        GenericResponse::new(status_code, message, T::default())
+    }
+}
+
+impl<T: Serialize> IntoResponse for GenericResponse<T> {
+    /// Encoded as JSON, or the binary format negotiated for the request
+    fn into_response(self) -> axum::response::Response {
+        render(&self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        id: u64,
+        name: String,
+    }
+
+    #[test]
+    fn test_ok_round_trip() {
+        let response = GenericResponse::ok(Payload { id: 7, name: "report.pdf".to_string() });
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(
+            json,
+            r#"{"status":"OK","status_code":0,"message":"OK","data":{"id":7,"name":"report.pdf"}}"#
+        );
+
+        let parsed: GenericResponse<Payload> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.status_code, STATUS_NO_ERROR);
+        assert_eq!(parsed, response);
+    }
+
+    #[test]
+    fn test_error() {
+        let response: GenericResponse<Empty> = GenericResponse::error(STATUS_NOT_FOUND, "no such file");
+        assert_eq!(response.status, STATUS_NOT_FOUND_STR);
+        assert_eq!(response.message, "no such file");
+        assert_eq!(serde_json::to_value(&response).unwrap()["data"], serde_json::json!({}));
+    }
 }
@@ -0,0 +1,214 @@
+//! Encodings of request and response bodies: JSON, MessagePack and CBOR.
+//!
+//! The response encoding is negotiated from `Accept` by the
+//! [`negotiate_format`](crate::middleware::format_middleware::negotiate_format) middleware
+//! and read back by [`render`] while the response is built. JSON stays the default.
+
+use std::future::Future;
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use log::error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use thiserror::Error;
+
+tokio::task_local! {
+    static RESPONSE_FORMAT: BodyFormat;
+}
+
+#[derive(Error, Debug)]
+#[error("the {format:?} body could not be {action}: {message}")]
+pub struct FormatError {
+    pub format: BodyFormat,
+    pub action: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BodyFormat {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl BodyFormat {
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            BodyFormat::MessagePack => "application/msgpack",
+            BodyFormat::Cbor => "application/cbor",
+        }
+    }
+
+    /// Format of a media type without its parameters
+    pub fn from_media_type(essence: &str) -> Option<Self> {
+        match essence.trim().to_ascii_lowercase().as_str() {
+            "application/json" => Some(BodyFormat::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(BodyFormat::MessagePack)
+            }
+            "application/cbor" => Some(BodyFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Preferred format of the `Accept` header, by quality then order. Wildcards and
+    /// unsupported media types fall back to JSON.
+    pub fn from_accept(headers: &HeaderMap) -> Self {
+        let mut best: Option<(BodyFormat, f32)> = None;
+        let media_ranges = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+
+        for media_range in media_ranges {
+            let mut params = media_range.split(';').map(str::trim);
+            let essence = params.next().unwrap_or_default();
+            let quality = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            let format = match essence {
+                "*/*" | "application/*" => Some(BodyFormat::Json),
+                essence => BodyFormat::from_media_type(essence),
+            };
+            if let Some(format) = format {
+                if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                    best = Some((format, quality));
+                }
+            }
+        }
+        best.map(|(format, _)| format).unwrap_or_default()
+    }
+
+    /// Format declared by the `Content-Type` header, `None` when it is missing or unsupported
+    pub fn from_content_type(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        let essence = content_type.split(';').next().unwrap_or_default();
+        if essence.trim().to_ascii_lowercase().ends_with("+json") {
+            return Some(BodyFormat::Json);
+        }
+        BodyFormat::from_media_type(essence)
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, FormatError> {
+        let encoded = match self {
+            BodyFormat::Json => serde_json::to_vec(value).map_err(|err| err.to_string()),
+            BodyFormat::MessagePack => rmp_serde::to_vec_named(value).map_err(|err| err.to_string()),
+            BodyFormat::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(value, &mut buf).map(|_| buf).map_err(|err| err.to_string())
+            }
+        };
+        encoded.map_err(|message| FormatError {
+            format: self,
+            action: "encoded",
+            message,
+        })
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, FormatError> {
+        let decoded = match self {
+            BodyFormat::Json => serde_json::from_slice(bytes).map_err(|err| err.to_string()),
+            BodyFormat::MessagePack => rmp_serde::from_slice(bytes).map_err(|err| err.to_string()),
+            BodyFormat::Cbor => ciborium::from_reader(bytes).map_err(|err| err.to_string()),
+        };
+        decoded.map_err(|message| FormatError {
+            format: self,
+            action: "decoded",
+            message,
+        })
+    }
+}
+
+/// Run `f` with `format` as the encoding of the responses it renders
+pub async fn with_response_format<F: Future>(format: BodyFormat, f: F) -> F::Output {
+    RESPONSE_FORMAT.scope(format, f).await
+}
+
+/// Encoding negotiated for the current request
+pub fn response_format() -> BodyFormat {
+    RESPONSE_FORMAT.try_with(|format| *format).unwrap_or_default()
+}
+
+/// Encode `value` in the negotiated format
+pub fn render<T: Serialize>(value: &T) -> Response {
+    let format = response_format();
+    match format.encode(value) {
+        Ok(body) => ([(header::CONTENT_TYPE, HeaderValue::from_static(format.content_type()))], body).into_response(),
+        Err(err) => {
+            error!("{}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        id: u64,
+        name: String,
+    }
+
+    fn accept(value: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_static(value))])
+    }
+
+    #[test]
+    fn test_from_accept() {
+        assert_eq!(BodyFormat::from_accept(&HeaderMap::new()), BodyFormat::Json);
+        assert_eq!(BodyFormat::from_accept(&accept("application/msgpack")), BodyFormat::MessagePack);
+        assert_eq!(
+            BodyFormat::from_accept(&accept("application/json;q=0.5, application/cbor")),
+            BodyFormat::Cbor
+        );
+        assert_eq!(
+            BodyFormat::from_accept(&accept("application/cbor, application/msgpack")),
+            BodyFormat::Cbor
+        );
+        assert_eq!(BodyFormat::from_accept(&accept("application/cbor;q=0, */*")), BodyFormat::Json);
+        assert_eq!(BodyFormat::from_accept(&accept("text/html")), BodyFormat::Json);
+    }
+
+    #[test]
+    fn test_from_content_type() {
+        let content_type = |value| HeaderMap::from_iter([(header::CONTENT_TYPE, HeaderValue::from_static(value))]);
+        assert_eq!(
+            BodyFormat::from_content_type(&content_type("application/json; charset=utf-8")),
+            Some(BodyFormat::Json)
+        );
+        assert_eq!(
+            BodyFormat::from_content_type(&content_type("application/x-msgpack")),
+            Some(BodyFormat::MessagePack)
+        );
+        assert_eq!(BodyFormat::from_content_type(&content_type("text/plain")), None);
+        assert_eq!(BodyFormat::from_content_type(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let payload = Payload { id: 7, name: "report.pdf".to_string() };
+        for format in [BodyFormat::Json, BodyFormat::MessagePack, BodyFormat::Cbor] {
+            let encoded = format.encode(&payload).unwrap();
+            assert_eq!(format.decode::<Payload>(&encoded).unwrap(), payload, "{:?}", format);
+        }
+        assert!(BodyFormat::Cbor.decode::<Payload>(b"{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_render() {
+        let response = render(&Payload { id: 1, name: "a".to_string() });
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let response = with_response_format(BodyFormat::Cbor, async { render(&1) }).await;
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/cbor");
+    }
+}
@@ -0,0 +1,112 @@
+//! [RFC 7807](https://www.rfc-editor.org/rfc/rfc7807) Problem Details rendering of errors
+//!
+//! [`ApiError`](crate::response::api_error::ApiError) responses carry a [`ProblemDetails`]
+//! in their extensions. The [`problem_details`](crate::middleware::problem_middleware::problem_details)
+//! middleware swaps the `GenericResponse` body for it when the client asked for
+//! `application/problem+json`.
+
+use axum::http::{header, HeaderMap, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::response::api_error::FieldError;
+use crate::response::status_code::status_info;
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProblemDetails {
+    /// URI reference identifying the problem type, pointing into the status code catalog
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    /// Application status code, as in `GenericResponse::status_code`
+    pub status_code: u16,
+    /// Fields which failed validation
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+impl ProblemDetails {
+    pub fn new(status: StatusCode, status_code: u16, detail: Option<String>) -> Self {
+        let (problem_type, title) = match status_info(status_code) {
+            Some(info) => (format!("/meta/status-codes#{}", info.id), info.title.to_string()),
+            None => ("about:blank".to_string(), status.canonical_reason().unwrap_or_default().to_string()),
+        };
+        ProblemDetails {
+            problem_type,
+            title,
+            status: status.as_u16(),
+            detail,
+            instance: None,
+            status_code,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Whether the `Accept` header asks for Problem Details rather than the default envelope
+pub fn accepts_problem_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_range| {
+            let mut params = media_range.split(';').map(str::trim);
+            let essence = params.next().unwrap_or_default();
+            let rejected = params.any(|param| {
+                param
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    .is_some_and(|q| q == 0.0)
+            });
+            essence.eq_ignore_ascii_case(PROBLEM_JSON) && !rejected
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use crate::response::status_code::*;
+
+    fn accept(value: &'static str) -> HeaderMap {
+        HeaderMap::from_iter([(header::ACCEPT, HeaderValue::from_static(value))])
+    }
+
+    #[test]
+    fn test_accepts_problem_json() {
+        assert!(accepts_problem_json(&accept("application/problem+json")));
+        assert!(accepts_problem_json(&accept("application/json;q=0.5, Application/Problem+JSON")));
+        assert!(!accepts_problem_json(&accept("application/problem+json;q=0")));
+        assert!(!accepts_problem_json(&accept("application/json, */*")));
+        assert!(!accepts_problem_json(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut problem = ProblemDetails::new(
+            StatusCode::FORBIDDEN,
+            STATUS_QUOTA_EXCEEDED,
+            Some("the storage quota of the owner is exceeded".to_string()),
+        );
+        problem.instance = Some("/files".to_string());
+
+        assert_eq!(
+            serde_json::to_value(&problem).unwrap(),
+            serde_json::json!({
+                "type": "/meta/status-codes#quota_exceeded",
+                "title": STATUS_QUOTA_EXCEEDED_STR,
+                "status": 403,
+                "detail": "the storage quota of the owner is exceeded",
+                "instance": "/files",
+                "status_code": STATUS_QUOTA_EXCEEDED,
+            })
+        );
+    }
+}
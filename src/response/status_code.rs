@@ -0,0 +1,209 @@
+//! Catalog of the application status codes reported in `GenericResponse::status_code`.
+//!
+//! Clients branch on these codes, so a published code never changes its value or identifier.
+//! Codes are allocated in the range of their [`StatusDomain`]; only the codes 0 to 3 predate
+//! the domain ranges and keep their historical values.
+
+use serde::{Deserialize, Serialize};
+
+pub const STATUS_NO_ERROR: u16 = 0;
+pub const STATUS_BAD_REQUEST: u16 = 1;
+pub const STATUS_REQUEST_TIMEOUT_ERROR: u16 = 2;
+pub const STATUS_INTERNAL_SERVER_ERROR: u16 = 3;
+pub const STATUS_SERVICE_UNAVAILABLE: u16 = 100;
+pub const STATUS_NOT_FOUND: u16 = 101;
+pub const STATUS_CONFLICT: u16 = 102;
+pub const STATUS_RATE_LIMITED: u16 = 103;
+pub const STATUS_UPSTREAM_ERROR: u16 = 104;
+pub const STATUS_UNAUTHORIZED: u16 = 1000;
+pub const STATUS_FORBIDDEN: u16 = 1001;
+pub const STATUS_RANGE_NOT_SATISFIABLE: u16 = 2000;
+pub const STATUS_PAYLOAD_TOO_LARGE: u16 = 2001;
+pub const STATUS_CHECKSUM_MISMATCH: u16 = 2002;
+pub const STATUS_CONTENT_TYPE_NOT_ALLOWED: u16 = 2003;
+pub const STATUS_QUOTA_EXCEEDED: u16 = 2004;
+pub const STATUS_VALIDATION_FAILED: u16 = 4000;
+pub const STATUS_MALFORMED_REQUEST: u16 = 4001;
+pub const STATUS_UNSUPPORTED_MEDIA_TYPE: u16 = 4002;
+
+/// Last of the codes published before the domain ranges, which keep their historical values
+const LAST_LEGACY_STATUS_CODE: u16 = STATUS_INTERNAL_SERVER_ERROR;
+
+pub const STATUS_NO_ERROR_STR: &str = "OK";
+pub const STATUS_BAD_REQUEST_STR: &str = "Bad Request";
+pub const STATUS_REQUEST_TIMEOUT_ERROR_STR: &str = "Request Timeout";
+pub const STATUS_INTERNAL_SERVER_ERROR_STR: &str = "Internal Server Error";
+pub const STATUS_NOT_FOUND_STR: &str = "Not Found";
+pub const STATUS_RANGE_NOT_SATISFIABLE_STR: &str = "Range Not Satisfiable";
+pub const STATUS_UNAUTHORIZED_STR: &str = "Unauthorized";
+pub const STATUS_PAYLOAD_TOO_LARGE_STR: &str = "Payload Too Large";
+pub const STATUS_CHECKSUM_MISMATCH_STR: &str = "Checksum Mismatch";
+pub const STATUS_CONTENT_TYPE_NOT_ALLOWED_STR: &str = "Content Type Not Allowed";
+pub const STATUS_FORBIDDEN_STR: &str = "Forbidden";
+pub const STATUS_QUOTA_EXCEEDED_STR: &str = "Quota Exceeded";
+pub const STATUS_CONFLICT_STR: &str = "Conflict";
+pub const STATUS_RATE_LIMITED_STR: &str = "Too Many Requests";
+pub const STATUS_UPSTREAM_ERROR_STR: &str = "Upstream Error";
+pub const STATUS_SERVICE_UNAVAILABLE_STR: &str = "Service Unavailable";
+pub const STATUS_VALIDATION_FAILED_STR: &str = "Validation Failed";
+pub const STATUS_MALFORMED_REQUEST_STR: &str = "Malformed Request";
+pub const STATUS_UNSUPPORTED_MEDIA_TYPE_STR: &str = "Unsupported Media Type";
+
+/// Area of the API a status code belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusDomain {
+    General,
+    Auth,
+    Storage,
+    User,
+    Validation,
+}
+
+impl StatusDomain {
+    pub const ALL: [StatusDomain; 5] = [
+        StatusDomain::General,
+        StatusDomain::Auth,
+        StatusDomain::Storage,
+        StatusDomain::User,
+        StatusDomain::Validation,
+    ];
+
+    /// Inclusive range new codes of the domain are allocated in
+    pub const fn range(self) -> (u16, u16) {
+        match self {
+            StatusDomain::General => (100, 999),
+            StatusDomain::Auth => (1000, 1999),
+            StatusDomain::Storage => (2000, 2999),
+            StatusDomain::User => (3000, 3999),
+            StatusDomain::Validation => (4000, 4999),
+        }
+    }
+}
+
+/// An entry of the catalog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StatusCodeInfo {
+    pub code: u16,
+    /// Stable identifier, for clients which prefer not to depend on the numeric value
+    pub id: &'static str,
+    pub domain: StatusDomain,
+    /// Default message of the code
+    pub title: &'static str,
+}
+
+const fn entry(code: u16, id: &'static str, domain: StatusDomain, title: &'static str) -> StatusCodeInfo {
+    StatusCodeInfo { code, id, domain, title }
+}
+
+pub const STATUS_CODES: &[StatusCodeInfo] = &[
+    entry(STATUS_NO_ERROR, "ok", StatusDomain::General, STATUS_NO_ERROR_STR),
+    entry(STATUS_BAD_REQUEST, "bad_request", StatusDomain::Validation, STATUS_BAD_REQUEST_STR),
+    entry(STATUS_REQUEST_TIMEOUT_ERROR, "request_timeout", StatusDomain::General, STATUS_REQUEST_TIMEOUT_ERROR_STR),
+    entry(STATUS_INTERNAL_SERVER_ERROR, "internal_error", StatusDomain::General, STATUS_INTERNAL_SERVER_ERROR_STR),
+    entry(STATUS_SERVICE_UNAVAILABLE, "service_unavailable", StatusDomain::General, STATUS_SERVICE_UNAVAILABLE_STR),
+    entry(STATUS_NOT_FOUND, "not_found", StatusDomain::General, STATUS_NOT_FOUND_STR),
+    entry(STATUS_CONFLICT, "conflict", StatusDomain::General, STATUS_CONFLICT_STR),
+    entry(STATUS_RATE_LIMITED, "rate_limited", StatusDomain::General, STATUS_RATE_LIMITED_STR),
+    entry(STATUS_UPSTREAM_ERROR, "upstream_error", StatusDomain::General, STATUS_UPSTREAM_ERROR_STR),
+    entry(STATUS_UNAUTHORIZED, "unauthorized", StatusDomain::Auth, STATUS_UNAUTHORIZED_STR),
+    entry(STATUS_FORBIDDEN, "forbidden", StatusDomain::Auth, STATUS_FORBIDDEN_STR),
+    entry(STATUS_RANGE_NOT_SATISFIABLE, "range_not_satisfiable", StatusDomain::Storage, STATUS_RANGE_NOT_SATISFIABLE_STR),
+    entry(STATUS_PAYLOAD_TOO_LARGE, "payload_too_large", StatusDomain::Storage, STATUS_PAYLOAD_TOO_LARGE_STR),
+    entry(STATUS_CHECKSUM_MISMATCH, "checksum_mismatch", StatusDomain::Storage, STATUS_CHECKSUM_MISMATCH_STR),
+    entry(STATUS_CONTENT_TYPE_NOT_ALLOWED, "content_type_not_allowed", StatusDomain::Storage, STATUS_CONTENT_TYPE_NOT_ALLOWED_STR),
+    entry(STATUS_QUOTA_EXCEEDED, "quota_exceeded", StatusDomain::Storage, STATUS_QUOTA_EXCEEDED_STR),
+    entry(STATUS_VALIDATION_FAILED, "validation_failed", StatusDomain::Validation, STATUS_VALIDATION_FAILED_STR),
+    entry(STATUS_MALFORMED_REQUEST, "malformed_request", StatusDomain::Validation, STATUS_MALFORMED_REQUEST_STR),
+    entry(STATUS_UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", StatusDomain::Validation, STATUS_UNSUPPORTED_MEDIA_TYPE_STR),
+];
+
+/// Fails the build when two entries share a code or an identifier, or a code other than the
+/// legacy ones is outside the range of its domain
+const _: () = check_catalog(STATUS_CODES);
+
+const fn check_catalog(catalog: &[StatusCodeInfo]) {
+    let mut i = 0;
+    while i < catalog.len() {
+        let (min, max) = catalog[i].domain.range();
+        if catalog[i].code > LAST_LEGACY_STATUS_CODE && (catalog[i].code < min || catalog[i].code > max) {
+            panic!("status code outside the range of its domain");
+        }
+
+        let mut j = i + 1;
+        while j < catalog.len() {
+            if catalog[i].code == catalog[j].code {
+                panic!("duplicate status code in the catalog");
+            }
+            if str_eq(catalog[i].id, catalog[j].id) {
+                panic!("duplicate status code identifier in the catalog");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Catalog entry of `code`
+pub fn status_info(code: u16) -> Option<&'static StatusCodeInfo> {
+    STATUS_CODES.iter().find(|info| info.code == code)
+}
+
+/// Status string mapped to `code`
+pub fn status_str(code: u16) -> &'static str {
+    status_info(code).map_or(STATUS_INTERNAL_SERVER_ERROR_STR, |info| info.title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_info() {
+        let info = status_info(STATUS_QUOTA_EXCEEDED).unwrap();
+        assert_eq!(info.id, "quota_exceeded");
+        assert_eq!(info.domain, StatusDomain::Storage);
+        assert_eq!(status_str(STATUS_UNAUTHORIZED), STATUS_UNAUTHORIZED_STR);
+        assert_eq!(status_str(999), STATUS_INTERNAL_SERVER_ERROR_STR);
+    }
+
+    #[test]
+    fn test_codes_in_their_domain_range() {
+        for info in STATUS_CODES.iter().filter(|info| info.code > LAST_LEGACY_STATUS_CODE) {
+            let (min, max) = info.domain.range();
+            assert!((min..=max).contains(&info.code), "{} is outside its domain", info.id);
+        }
+    }
+
+    #[test]
+    fn test_str_eq() {
+        assert!(str_eq("not_found", "not_found"));
+        assert!(!str_eq("not_found", "not_foun"));
+        assert!(!str_eq("conflict", "conflicu"));
+    }
+
+    #[test]
+    fn test_domain_ranges_do_not_overlap() {
+        for (i, a) in StatusDomain::ALL.iter().enumerate() {
+            for b in &StatusDomain::ALL[i + 1..] {
+                let ((a_min, a_max), (b_min, b_max)) = (a.range(), b.range());
+                assert!(a_max < b_min || b_max < a_min, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+}
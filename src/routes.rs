@@ -0,0 +1,70 @@
+use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+
+use crate::constants::metrics_constants::METRICS_PATH;
+use crate::constants::request_constants::MAX_BUFFERED_BODY_BYTES;
+use crate::handler::*;
+use crate::middleware::access_log_middleware::access_log;
+use crate::middleware::auth_middleware::{require_admin, require_auth};
+use crate::middleware::format_middleware::negotiate_format;
+use crate::middleware::locale_middleware::negotiate_language;
+use crate::middleware::metrics_middleware::track_metrics;
+use crate::middleware::problem_middleware::problem_details;
+use crate::middleware::request_id_middleware::propagate_request_id;
+use crate::state::AppState;
+
+pub fn create_router(state: AppState) -> Router {
+    let admin = Router::new()
+        .route("/admin/usage", get(usage_handler::list_usage))
+        .route("/admin/usage/:owner_id", get(usage_handler::get_owner_usage))
+        .route("/admin/usage/:owner_id/quota", put(usage_handler::set_owner_quota))
+        .route(
+            "/admin/log-level",
+            get(log_level_handler::get_log_level).put(log_level_handler::set_log_level),
+        )
+        .route_layer(middleware::from_fn(require_admin));
+
+    let protected = Router::new()
+        .route("/files", post(file_handler::upload_file))
+        .route("/files/presigned", post(file_handler::create_presigned_upload))
+        .route("/files/:file_id/complete", post(file_handler::complete_presigned_upload))
+        .route("/files/archive", post(archive_handler::create_archive))
+        .route(
+            "/files/:file_id",
+            get(file_handler::download_file).delete(file_handler::delete_file),
+        )
+        .route("/files/:file_id/variants/:name", get(file_handler::download_variant))
+        .route("/me/usage", get(usage_handler::get_my_usage))
+        .merge(admin)
+        .route_layer(middleware::from_fn(require_auth));
+
+    Router::new()
+        .route("/", get(version_handler::get_version))
+        .route("/health/live", get(health_handler::get_live))
+        .route("/health/ready", get(health_handler::get_ready))
+        .route("/meta/status-codes", get(meta_handler::list_status_codes))
+        .route(METRICS_PATH, get(metrics_handler::get_metrics))
+        .merge(protected)
+        .layer(DefaultBodyLimit::max(MAX_BUFFERED_BODY_BYTES))
+        .layer(middleware::from_fn(problem_details))
+        .layer(middleware::from_fn(negotiate_format))
+        .layer(middleware::from_fn(negotiate_language))
+        .layer(middleware::from_fn(access_log))
+        .layer(middleware::from_fn(track_metrics))
+        .layer(middleware::from_fn(propagate_request_id))
+        .with_state(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_router() {
+        let _router = create_router(AppState::from_env());
+    }
+}
@@ -1 +1 @@
-mod client;
\ No newline at end of file
+pub mod client;
\ No newline at end of file
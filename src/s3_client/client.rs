@@ -1,38 +1,172 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::time::Duration;
 
 use aws_config::Region;
 use aws_sdk_s3::{Client, Config};
 use aws_credential_types::Credentials;
-use aws_sdk_s3::operation::list_buckets::{ListBucketsError, ListBucketsOutput};
-use serde::{Deserialize, Serialize};
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
+use aws_sdk_s3::operation::head_bucket::{HeadBucketError, HeadBucketOutput};
+use aws_sdk_s3::operation::head_object::{HeadObjectError, HeadObjectOutput};
+use aws_sdk_s3::presigning::{PresignedRequest, PresigningConfig};
+use aws_sdk_s3::primitives::ByteStream;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct S33Client {
+use crate::constants::s3_constants::*;
+use crate::metrics::observe_s3;
+
+/// Thin wrapper around the S3 client bound to the bucket that stores the uploaded files. The
+/// latency and failures of the operations sent to the object store are recorded.
+#[derive(Debug, Clone)]
+pub struct S3Client {
     client: Client,
+    bucket: String,
 }
 
+impl S3Client {
+    /// Build the client from the `S3_*` environment variables, falling back to the local MinIO defaults
+    pub fn from_env() -> Self {
+        let endpoint = env::var(S3_ENDPOINT_ENV).unwrap_or(DEFAULT_S3_ENDPOINT.to_string());
+        let access_key = env::var(S3_ACCESS_KEY_ENV).unwrap_or(DEFAULT_S3_ACCESS_KEY.to_string());
+        let secret_key = env::var(S3_SECRET_KEY_ENV).unwrap_or(DEFAULT_S3_SECRET_KEY.to_string());
+        let region = env::var(S3_REGION_ENV).unwrap_or(DEFAULT_S3_REGION.to_string());
+        let bucket = env::var(S3_BUCKET_ENV).unwrap_or(DEFAULT_S3_BUCKET.to_string());
 
-pub async fn new_client() {
-    let creds = Credentials::from(Credentials::new("minioadmin", "minioadmin", None, None, ""));
-    let config = Config::builder()
-        .endpoint_url("http://127.0.0.1:9000")
-        .credentials_provider(creds)
-        .region(Region::new("us-east-1"))
-        .build();
+        let creds = Credentials::new(access_key, secret_key, None, None, "");
+        let config = Config::builder()
+            .endpoint_url(endpoint)
+            .credentials_provider(creds)
+            .region(Region::new(region))
+            .force_path_style(true)
+            .build();
 
-    let client = Client::from_conf(config);
+        S3Client {
+            client: Client::from_conf(config),
+            bucket,
+        }
+    }
+
+    /// Check the bucket exists and is reachable with the configured credentials
+    pub async fn head_bucket(&self) -> Result<HeadBucketOutput, SdkError<HeadBucketError>> {
+        observe_s3("head_bucket", self.client.head_bucket().bucket(&self.bucket).send()).await
+    }
 
+    /// Fetch the metadata (size, ETag, last-modified, content type) of an object
+    pub async fn head_object(&self, key: &str) -> Result<HeadObjectOutput, SdkError<HeadObjectError>> {
+        observe_s3(
+            "head_object",
+            self.client
+                .head_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .send(),
+        )
+        .await
+    }
 
+    /// Fetch an object, optionally restricted to a `bytes=` range.
+    ///
+    /// When `if_match` is set the object store rejects the request if the object changed in the
+    /// meantime, which keeps the parts of a multi-range response consistent.
+    pub async fn get_object(
+        &self,
+        key: &str,
+        range: Option<String>,
+        if_match: Option<String>,
+    ) -> Result<GetObjectOutput, SdkError<GetObjectError>> {
+        observe_s3(
+            "get_object",
+            self.client
+                .get_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .set_range(range)
+                .set_if_match(if_match)
+                .send(),
+        )
+        .await
+    }
 
-    let b_res = client.list_buckets().send().await;
-    match b_res {
-        Ok(b) => {
-            println!("{:?}", b)
-        }
-        Err(err) => {
-            panic!("{}", err)
-        }
+    /// Upload the content of a local file as a new object.
+    ///
+    /// `content_md5` is forwarded so the object store also verifies the integrity of the transfer.
+    pub async fn put_object_from_path(
+        &self,
+        key: &str,
+        path: &Path,
+        content_type: &str,
+        content_md5: &str,
+        metadata: HashMap<String, String>,
+    ) -> anyhow::Result<()> {
+        let body = ByteStream::from_path(path).await?;
+        observe_s3(
+            "put_object",
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .body(body)
+                .content_type(content_type)
+                .content_md5(content_md5)
+                .set_metadata(Some(metadata))
+                .send(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Upload an in-memory object
+    pub async fn put_object_bytes(&self, key: &str, body: Vec<u8>, content_type: &str) -> anyhow::Result<()> {
+        observe_s3(
+            "put_object",
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .body(ByteStream::from(body))
+                .content_type(content_type)
+                .send(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Presign a `PUT` of exactly `content_length` bytes the client uploads directly to the store
+    pub async fn presign_put_object(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_length: i64,
+        metadata: HashMap<String, String>,
+        expires_in: Duration,
+    ) -> anyhow::Result<PresignedRequest> {
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .content_length(content_length)
+            .set_metadata(Some(metadata))
+            .presigned(PresigningConfig::expires_in(expires_in)?)
+            .await?;
+        Ok(request)
     }
 
+    /// Delete an object. Deleting a missing object is not an error.
+    pub async fn delete_object(&self, key: &str) -> anyhow::Result<()> {
+        observe_s3(
+            "delete_object",
+            self.client
+                .delete_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .send(),
+        )
+        .await?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -41,10 +175,22 @@ mod tests {
 
     #[tokio::test]
     async fn test_new_client() {
-        new_client().await
+        let client = S3Client::from_env();
+        assert!(!client.bucket.is_empty());
     }
 
-
+    #[tokio::test]
+    #[ignore = "requires a running MinIO instance"]
+    async fn test_list_buckets() {
+        let client = S3Client::from_env();
+        let b_res = client.client.list_buckets().send().await;
+        match b_res {
+            Ok(b) => {
+                println!("{:?}", b)
+            }
+            Err(err) => {
+                panic!("{}", err)
+            }
+        }
+    }
 }
-
-
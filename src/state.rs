@@ -0,0 +1,34 @@
+use crate::archive::ArchivePolicy;
+use crate::database::{new_pool, DbPool};
+use crate::s3_client::client::S3Client;
+use crate::upload::policy::UploadPolicy;
+use crate::upload::quota::QuotaPolicy;
+use crate::variants::VariantGenerator;
+
+/// Shared state handed to every handler
+#[derive(Clone)]
+pub struct AppState {
+    pub s3_client: S3Client,
+    pub db_pool: DbPool,
+    pub upload_policy: UploadPolicy,
+    pub quota_policy: QuotaPolicy,
+    pub archive_policy: ArchivePolicy,
+    pub variant_generator: VariantGenerator,
+    /// Latest migration applied to the database, read once the migrations have run at startup
+    pub migration_version: Option<String>,
+}
+
+impl AppState {
+    pub fn from_env() -> Self {
+        let s3_client = S3Client::from_env();
+        AppState {
+            variant_generator: VariantGenerator::from_env(s3_client.clone()),
+            s3_client,
+            db_pool: new_pool(),
+            upload_policy: UploadPolicy::from_env(),
+            quota_policy: QuotaPolicy::from_env(),
+            archive_policy: ArchivePolicy::from_env(),
+            migration_version: None,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use thiserror::Error;
+
+use crate::database::DbError;
+use crate::response::api_error::ApiError;
+use crate::response::api_response::*;
+
+pub mod integrity;
+pub mod policy;
+pub mod quota;
+pub mod sniff;
+pub mod spool;
+pub mod sweeper;
+
+/// Reasons an upload is rejected before the object is committed to the storage backend
+#[derive(Error, Debug)]
+pub enum UploadError {
+    #[error("the upload exceeds the maximum size of {limit} bytes")]
+    TooLarge { limit: u64 },
+
+    #[error("the {0} header is not a valid checksum")]
+    InvalidChecksumHeader(&'static str),
+
+    #[error("the {0} checksum of the uploaded content does not match the supplied value")]
+    ChecksumMismatch(&'static str),
+
+    #[error("the file name exceeds {limit} characters")]
+    FileNameTooLong { limit: usize },
+
+    #[error("the content type {0} is not allowed")]
+    ContentTypeNotAllowed(String),
+
+    #[error("the uploaded content is {detected}, not the declared {declared}")]
+    ContentTypeMismatch { declared: String, detected: String },
+
+    #[error("the storage quota of the owner is exceeded")]
+    QuotaExceeded,
+
+    #[error("the request body could not be read: {0}")]
+    Body(String),
+
+    #[error("the upload could not be spooled: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("the upload could not be stored: {0}")]
+    Storage(#[from] anyhow::Error),
+
+    #[error("the upload could not be accounted: {0}")]
+    Database(#[from] DbError),
+}
+
+impl From<UploadError> for ApiError {
+    fn from(err: UploadError) -> Self {
+        let reason = err.to_string();
+        match err {
+            UploadError::TooLarge { limit } => ApiError::PayloadTooLarge { limit },
+            UploadError::InvalidChecksumHeader(_) | UploadError::FileNameTooLong { .. } | UploadError::Body(_) => {
+                ApiError::validation(reason)
+            }
+            UploadError::ChecksumMismatch(_) => ApiError::Validation {
+                status_code: STATUS_CHECKSUM_MISMATCH,
+                reason,
+            },
+            UploadError::ContentTypeNotAllowed(_) | UploadError::ContentTypeMismatch { .. } => ApiError::Validation {
+                status_code: STATUS_CONTENT_TYPE_NOT_ALLOWED,
+                reason,
+            },
+            UploadError::QuotaExceeded => ApiError::Forbidden {
+                status_code: STATUS_QUOTA_EXCEEDED,
+                reason,
+            },
+            UploadError::Storage(err) => ApiError::Upstream(err),
+            UploadError::Io(_) | UploadError::Database(_) => ApiError::Internal(err.into()),
+        }
+    }
+}
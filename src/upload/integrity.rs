@@ -0,0 +1,186 @@
+//! Verification of client-supplied checksums against the streamed content
+
+use axum::http::HeaderMap;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use crate::constants::upload_constants::{CONTENT_MD5_HEADER, CONTENT_SHA256_HEADER};
+use crate::upload::UploadError;
+
+const MD5: &str = "MD5";
+const SHA256: &str = "SHA-256";
+
+/// Checksums the client announced for the upload
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ExpectedChecksums {
+    pub md5: Option<Vec<u8>>,
+    pub sha256: Option<Vec<u8>>,
+}
+
+impl ExpectedChecksums {
+    /// Read `Content-MD5` (base64) and `X-Content-SHA256` (hex or base64) from the request
+    pub fn from_headers(headers: &HeaderMap) -> Result<Self, UploadError> {
+        let md5 = match headers.get(CONTENT_MD5_HEADER) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| STANDARD.decode(value.trim()).ok())
+                    .filter(|digest| digest.len() == 16)
+                    .ok_or(UploadError::InvalidChecksumHeader("Content-MD5"))?,
+            ),
+            None => None,
+        };
+
+        let sha256 = match headers.get(CONTENT_SHA256_HEADER) {
+            Some(value) => Some(
+                value
+                    .to_str()
+                    .ok()
+                    .and_then(|value| decode_hex_or_base64(value.trim()))
+                    .filter(|digest| digest.len() == 32)
+                    .ok_or(UploadError::InvalidChecksumHeader("X-Content-SHA256"))?,
+            ),
+            None => None,
+        };
+
+        Ok(ExpectedChecksums { md5, sha256 })
+    }
+
+    pub fn verify(&self, digests: &Digests) -> Result<(), UploadError> {
+        if self.md5.as_ref().is_some_and(|md5| *md5 != digests.md5) {
+            return Err(UploadError::ChecksumMismatch(MD5));
+        }
+        if self.sha256.as_ref().is_some_and(|sha256| *sha256 != digests.sha256) {
+            return Err(UploadError::ChecksumMismatch(SHA256));
+        }
+        Ok(())
+    }
+}
+
+/// Incremental hashing of the upload while it is streamed
+#[derive(Default)]
+pub struct UploadHasher {
+    md5: Md5,
+    sha256: Sha256,
+}
+
+impl UploadHasher {
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.md5.update(chunk);
+        self.sha256.update(chunk);
+    }
+
+    pub fn finalize(self) -> Digests {
+        Digests {
+            md5: self.md5.finalize().to_vec(),
+            sha256: self.sha256.finalize().to_vec(),
+        }
+    }
+}
+
+/// Digests of the received content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digests {
+    pub md5: Vec<u8>,
+    pub sha256: Vec<u8>,
+}
+
+impl Digests {
+    pub fn md5_base64(&self) -> String {
+        STANDARD.encode(&self.md5)
+    }
+
+    pub fn sha256_hex(&self) -> String {
+        hex::encode(&self.sha256)
+    }
+}
+
+fn decode_hex_or_base64(value: &str) -> Option<Vec<u8>> {
+    if value.len() == 64 {
+        if let Ok(digest) = hex::decode(value) {
+            return Some(digest);
+        }
+    }
+    STANDARD.decode(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    // Digests of "hello world"
+    const MD5_B64: &str = "XrY7u+Ae7tCTyyK7j1rNww==";
+    const SHA256_HEX: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    fn digests() -> Digests {
+        let mut hasher = UploadHasher::default();
+        hasher.update(b"hello ");
+        hasher.update(b"world");
+        hasher.finalize()
+    }
+
+    #[test]
+    fn test_digests() {
+        let digests = digests();
+        assert_eq!(digests.md5_base64(), MD5_B64);
+        assert_eq!(digests.sha256_hex(), SHA256_HEX);
+    }
+
+    #[test]
+    fn test_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ExpectedChecksums::from_headers(&headers).unwrap(), ExpectedChecksums::default());
+
+        headers.insert(CONTENT_MD5_HEADER, HeaderValue::from_static(MD5_B64));
+        headers.insert(CONTENT_SHA256_HEADER, HeaderValue::from_static(SHA256_HEX));
+        let expected = ExpectedChecksums::from_headers(&headers).unwrap();
+        assert!(expected.verify(&digests()).is_ok());
+
+        let sha256_b64 = STANDARD.encode(hex::decode(SHA256_HEX).unwrap());
+        headers.insert(CONTENT_SHA256_HEADER, HeaderValue::from_str(&sha256_b64).unwrap());
+        let expected = ExpectedChecksums::from_headers(&headers).unwrap();
+        assert!(expected.verify(&digests()).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_MD5_HEADER, HeaderValue::from_static("not base64!"));
+        assert!(matches!(
+            ExpectedChecksums::from_headers(&headers),
+            Err(UploadError::InvalidChecksumHeader("Content-MD5"))
+        ));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_SHA256_HEADER, HeaderValue::from_static("abcd"));
+        assert!(matches!(
+            ExpectedChecksums::from_headers(&headers),
+            Err(UploadError::InvalidChecksumHeader("X-Content-SHA256"))
+        ));
+    }
+
+    #[test]
+    fn test_verify_mismatch() {
+        let expected = ExpectedChecksums {
+            md5: Some(vec![0; 16]),
+            sha256: None,
+        };
+        assert!(matches!(
+            expected.verify(&digests()),
+            Err(UploadError::ChecksumMismatch(MD5))
+        ));
+
+        let expected = ExpectedChecksums {
+            md5: None,
+            sha256: Some(vec![0; 32]),
+        };
+        assert!(matches!(
+            expected.verify(&digests()),
+            Err(UploadError::ChecksumMismatch(SHA256))
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::authentication::role::Role;
+use crate::constants::upload_constants::*;
+use crate::util::env::env_parse;
+
+/// Per-deployment rules applied to every upload
+#[derive(Debug, Clone)]
+pub struct UploadPolicy {
+    /// Allowed MIME types, either exact (`image/png`) or wildcards (`image/*`, `*/*`)
+    pub allowed_mime_types: Vec<String>,
+    pub max_size_user: u64,
+    pub max_size_admin: u64,
+    /// Directory the request bodies are spooled to while they are verified
+    pub tmp_dir: PathBuf,
+    /// Validity of the presigned upload URLs
+    pub presigned_expiry: Duration,
+}
+
+impl UploadPolicy {
+    pub fn from_env() -> Self {
+        let allowed_mime_types = env::var(UPLOAD_ALLOWED_MIME_TYPES_ENV)
+            .unwrap_or(DEFAULT_UPLOAD_ALLOWED_MIME_TYPES.to_string());
+
+        UploadPolicy {
+            allowed_mime_types: parse_mime_list(&allowed_mime_types),
+            max_size_user: env_parse(UPLOAD_MAX_SIZE_USER_ENV, DEFAULT_UPLOAD_MAX_SIZE_USER),
+            max_size_admin: env_parse(UPLOAD_MAX_SIZE_ADMIN_ENV, DEFAULT_UPLOAD_MAX_SIZE_ADMIN),
+            tmp_dir: env::var(UPLOAD_TMP_DIR_ENV).map(PathBuf::from).unwrap_or(env::temp_dir()),
+            presigned_expiry: Duration::from_secs(env_parse(
+                PRESIGNED_UPLOAD_EXPIRY_SECS_ENV,
+                DEFAULT_PRESIGNED_UPLOAD_EXPIRY_SECS,
+            )),
+        }
+    }
+
+    pub fn max_size_for(&self, role: &Role) -> u64 {
+        match role {
+            Role::Admin => self.max_size_admin,
+            Role::User => self.max_size_user,
+        }
+    }
+
+    pub fn is_allowed(&self, mime: &str) -> bool {
+        let (mime_type, _) = mime.split_once('/').unwrap_or((mime, ""));
+        self.allowed_mime_types.iter().any(|allowed| {
+            allowed == "*/*"
+                || allowed.eq_ignore_ascii_case(mime)
+                || allowed
+                    .strip_suffix("/*")
+                    .is_some_and(|allowed_type| allowed_type.eq_ignore_ascii_case(mime_type))
+        })
+    }
+}
+
+fn parse_mime_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|mime| !mime.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed: &str) -> UploadPolicy {
+        UploadPolicy {
+            allowed_mime_types: parse_mime_list(allowed),
+            max_size_user: 10,
+            max_size_admin: 100,
+            tmp_dir: env::temp_dir(),
+            presigned_expiry: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let images = policy("image/*, application/PDF ,");
+        assert!(images.is_allowed("image/png"));
+        assert!(images.is_allowed("application/pdf"));
+        assert!(!images.is_allowed("application/zip"));
+        assert!(!images.is_allowed("imagex/png"));
+
+        assert!(policy("*/*").is_allowed("application/zip"));
+    }
+
+    #[test]
+    fn test_max_size_for() {
+        let any = policy("*/*");
+        assert_eq!(any.max_size_for(&Role::User), 10);
+        assert_eq!(any.max_size_for(&Role::Admin), 100);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::constants::quota_constants::*;
+use crate::database::models::StorageUsage;
+use crate::util::env::env_parse;
+
+/// Default storage quota applied to owners without an override.
+///
+/// A limit of `None` means unlimited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaPolicy {
+    pub default_bytes: Option<i64>,
+    pub default_objects: Option<i64>,
+}
+
+impl QuotaPolicy {
+    /// Read the defaults from the environment. Non-positive values disable the limit.
+    pub fn from_env() -> Self {
+        QuotaPolicy {
+            default_bytes: env_limit(QUOTA_DEFAULT_BYTES_ENV, DEFAULT_QUOTA_BYTES),
+            default_objects: env_limit(QUOTA_DEFAULT_OBJECTS_ENV, DEFAULT_QUOTA_OBJECTS),
+        }
+    }
+
+    /// Byte and object limits applying to `usage`, per-owner overrides taking precedence
+    pub fn limits_for(&self, usage: &StorageUsage) -> (Option<i64>, Option<i64>) {
+        (
+            usage.quota_bytes.or(self.default_bytes),
+            usage.quota_objects.or(self.default_objects),
+        )
+    }
+
+    /// Whether storing `bytes` more in one more object would exceed the quota of `usage`
+    pub fn would_exceed(&self, usage: &StorageUsage, bytes: i64) -> bool {
+        let (max_bytes, max_objects) = self.limits_for(usage);
+        max_bytes.is_some_and(|max| usage.bytes_used.saturating_add(bytes) > max)
+            || max_objects.is_some_and(|max| usage.object_count + 1 > max)
+    }
+}
+
+fn env_limit(key: &str, default: i64) -> Option<i64> {
+    let limit = env_parse(key, default);
+    Some(limit).filter(|limit| *limit > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(bytes_used: i64, object_count: i64, quota_bytes: Option<i64>) -> StorageUsage {
+        StorageUsage {
+            owner_id: "tripg".to_string(),
+            bytes_used,
+            object_count,
+            quota_bytes,
+            quota_objects: None,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_would_exceed() {
+        let policy = QuotaPolicy {
+            default_bytes: Some(100),
+            default_objects: Some(2),
+        };
+
+        assert!(!policy.would_exceed(&usage(0, 0, None), 100));
+        assert!(policy.would_exceed(&usage(1, 0, None), 100));
+        assert!(policy.would_exceed(&usage(0, 2, None), 1));
+        assert!(!policy.would_exceed(&usage(100, 1, Some(1000)), 900));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let policy = QuotaPolicy {
+            default_bytes: None,
+            default_objects: None,
+        };
+        assert!(!policy.would_exceed(&usage(i64::MAX, 1_000_000, None), 1));
+        assert!(policy.would_exceed(&usage(10, 0, Some(10)), 1));
+    }
+}
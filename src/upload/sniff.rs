@@ -0,0 +1,73 @@
+//! Magic-byte content sniffing of uploaded files
+//!
+//! The declared `Content-Type` is only trusted for textual content, which has no signature.
+
+const OCTET_STREAM: &str = "application/octet-stream";
+const TEXT_PLAIN: &str = "text/plain";
+
+/// Detect the MIME type of an upload from its leading bytes
+pub fn sniff_content_type(prefix: &[u8], declared: Option<&str>) -> String {
+    if let Some(kind) = infer::get(prefix) {
+        return kind.mime_type().to_string();
+    }
+
+    if prefix.is_empty() || !is_utf8_prefix(prefix) {
+        return OCTET_STREAM.to_string();
+    }
+
+    match declared.map(essence) {
+        Some(declared) if is_textual(&declared) => declared,
+        _ => TEXT_PLAIN.to_string(),
+    }
+}
+
+/// Whether `prefix` is valid UTF-8, tolerating a multi-byte sequence cut at the end
+fn is_utf8_prefix(prefix: &[u8]) -> bool {
+    match std::str::from_utf8(prefix) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    }
+}
+
+/// The `type/subtype` part of a media type, lowercased and without parameters
+fn essence(media_type: &str) -> String {
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn is_textual(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || matches!(mime, "application/json" | "application/xml" | "application/x-ndjson")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_HEADER: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00];
+
+    #[test]
+    fn test_sniff_magic_bytes() {
+        assert_eq!(sniff_content_type(PNG_HEADER, None), "image/png");
+        assert_eq!(sniff_content_type(PNG_HEADER, Some("text/plain")), "image/png");
+        assert_eq!(sniff_content_type(b"%PDF-1.7\n", Some("image/png")), "application/pdf");
+    }
+
+    #[test]
+    fn test_sniff_text() {
+        assert_eq!(sniff_content_type(b"a,b\n1,2\n", Some("text/csv; charset=utf-8")), "text/csv");
+        assert_eq!(sniff_content_type(b"{\"a\":1}", Some("application/json")), "application/json");
+        assert_eq!(sniff_content_type(b"hello", Some("image/png")), "text/plain");
+        assert_eq!(sniff_content_type("h\u{e9}".as_bytes().split_last().unwrap().1, None), "text/plain");
+    }
+
+    #[test]
+    fn test_sniff_unknown_binary() {
+        assert_eq!(sniff_content_type(&[0xff, 0xfe, 0x00, 0x81], None), OCTET_STREAM);
+        assert_eq!(sniff_content_type(&[], Some("text/plain")), OCTET_STREAM);
+    }
+}
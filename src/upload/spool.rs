@@ -0,0 +1,118 @@
+//! Spooling of request bodies to a temporary file while they are hashed and size checked
+
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+use futures_util::{Stream, StreamExt};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+
+use crate::constants::upload_constants::SNIFF_PREFIX_LEN;
+use crate::upload::integrity::{Digests, UploadHasher};
+use crate::upload::UploadError;
+
+/// A fully received upload waiting to be committed. The spool file is removed on drop.
+#[derive(Debug)]
+pub struct SpooledUpload {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Leading bytes of the content, used for content sniffing
+    pub prefix: Vec<u8>,
+    pub digests: Digests,
+}
+
+impl Drop for SpooledUpload {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Removes a partially written spool file when the upload is rejected
+struct SpoolGuard(Option<PathBuf>);
+
+impl Drop for SpoolGuard {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Stream `body` into a new file under `tmp_dir`, failing as soon as it exceeds `max_size`
+pub async fn spool_body<S, E>(mut body: S, tmp_dir: &Path, max_size: u64) -> Result<SpooledUpload, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Display,
+{
+    let path = tmp_dir.join(format!("upload-{}", uuid::Uuid::new_v4()));
+    let mut guard = SpoolGuard(Some(path.clone()));
+    let mut file = File::create(&path).await?;
+
+    let mut hasher = UploadHasher::default();
+    let mut prefix = Vec::with_capacity(SNIFF_PREFIX_LEN);
+    let mut size: u64 = 0;
+
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|err| UploadError::Body(err.to_string()))?;
+
+        size += chunk.len() as u64;
+        if size > max_size {
+            return Err(UploadError::TooLarge { limit: max_size });
+        }
+
+        if prefix.len() < SNIFF_PREFIX_LEN {
+            let take = (SNIFF_PREFIX_LEN - prefix.len()).min(chunk.len());
+            prefix.extend_from_slice(&chunk[..take]);
+        }
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+
+    guard.0 = None;
+    Ok(SpooledUpload {
+        path,
+        size,
+        prefix,
+        digests: hasher.finalize(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+
+    fn body(chunks: Vec<&'static [u8]>) -> impl Stream<Item = Result<Bytes, String>> + Unpin {
+        stream::iter(chunks.into_iter().map(|chunk| Ok(Bytes::from_static(chunk))))
+    }
+
+    #[tokio::test]
+    async fn test_spool_body() {
+        let spooled = spool_body(body(vec![b"hello ", b"world"]), &std::env::temp_dir(), 11)
+            .await
+            .unwrap();
+
+        assert_eq!(spooled.size, 11);
+        assert_eq!(spooled.prefix, b"hello world");
+        assert_eq!(std::fs::read(&spooled.path).unwrap(), b"hello world");
+
+        let path = spooled.path.clone();
+        drop(spooled);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_spool_body_too_large() {
+        let result = spool_body(body(vec![b"hello ", b"world"]), &std::env::temp_dir(), 10).await;
+        assert!(matches!(result, Err(UploadError::TooLarge { limit: 10 })));
+    }
+
+    #[tokio::test]
+    async fn test_spool_body_error() {
+        let body = stream::iter(vec![Ok(Bytes::from_static(b"hello")), Err("connection reset")]);
+        let result = spool_body(body, &std::env::temp_dir(), 100).await;
+        assert!(matches!(result, Err(UploadError::Body(_))));
+    }
+}
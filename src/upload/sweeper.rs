@@ -0,0 +1,53 @@
+//! Release of the presigned uploads which were never confirmed or were deleted
+//!
+//! A presigned upload reserves quota when its URL is issued, and the object can be put through
+//! the URL until it expires, even after the file was deleted. Once the URL expired, the
+//! reservation of the uploads which were not confirmed, or were deleted, is given back and any
+//! object put through the URL is deleted.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use log::{error, info};
+
+use crate::constants::upload_constants::*;
+use crate::database::{usage_repository, with_connection, DbPool};
+use crate::s3_client::client::S3Client;
+
+/// Release the expired reservations every [`PENDING_UPLOAD_SWEEP_INTERVAL_SECS`] in a
+/// background task
+pub fn spawn_pending_upload_sweeper(db_pool: DbPool, s3_client: S3Client) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(PENDING_UPLOAD_SWEEP_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            match release_expired_uploads(&db_pool, &s3_client).await {
+                Ok(0) => {}
+                Ok(released) => info!("released {} expired presigned uploads", released),
+                Err(err) => error!("failed to release expired presigned uploads: {:#}", err),
+            }
+        }
+    });
+}
+
+/// Release up to [`PENDING_UPLOAD_SWEEP_BATCH_SIZE`] expired reservations, returning how many
+/// were released. A reservation whose object cannot be deleted is kept for the next sweep.
+pub async fn release_expired_uploads(db_pool: &DbPool, s3_client: &S3Client) -> anyhow::Result<usize> {
+    let cutoff = Utc::now() - chrono::Duration::seconds(PENDING_UPLOAD_SWEEP_GRACE_SECS);
+    let expired = with_connection(db_pool, move |conn| {
+        usage_repository::find_expired_files(conn, cutoff, PENDING_UPLOAD_SWEEP_BATCH_SIZE)
+    })
+    .await?;
+
+    let mut released = 0;
+    for file in expired {
+        if let Err(err) = s3_client.delete_object(&file.id.to_string()).await {
+            error!("failed to delete the object of expired upload {}: {:#}", file.id, err);
+            continue;
+        }
+        let file_id = file.id;
+        with_connection(db_pool, move |conn| usage_repository::release_file(conn, file_id)).await?;
+        released += 1;
+    }
+    Ok(released)
+}
@@ -0,0 +1,4 @@
+pub mod env;
+pub mod http_range;
+pub mod http_conditional;
+pub mod zip_stream;
\ No newline at end of file
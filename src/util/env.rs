@@ -0,0 +1,24 @@
+//! Configuration read from the environment
+
+use std::env;
+use std::str::FromStr;
+
+/// Value of the environment variable `key`, or `default` when it is unset or does not parse
+pub fn env_parse<T: FromStr>(key: &str, default: T) -> T {
+    env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_parse() {
+        env::set_var("UTIL_ENV_TEST_PARSED", "42");
+        env::set_var("UTIL_ENV_TEST_INVALID", "forty-two");
+
+        assert_eq!(env_parse("UTIL_ENV_TEST_PARSED", 7u32), 42);
+        assert_eq!(env_parse("UTIL_ENV_TEST_INVALID", 7u32), 7);
+        assert_eq!(env_parse("UTIL_ENV_TEST_UNSET", 7u32), 7);
+    }
+}
@@ -0,0 +1,87 @@
+//! Evaluation of HTTP conditional request headers
+//!
+//! Implements the subset of [RFC 9110 section 13](https://www.rfc-editor.org/rfc/rfc9110#section-13)
+//! needed for file downloads: `If-None-Match`, `If-Modified-Since` and `If-Range`.
+
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+
+/// Weak comparison of an `If-None-Match` header against the current entity tag
+pub fn if_none_match_matches(header: &str, etag: &str) -> bool {
+    let header = header.trim();
+    if header == "*" {
+        return true;
+    }
+
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| strip_weak(candidate) == strip_weak(etag))
+}
+
+/// Whether the representation has not changed since the `If-Modified-Since` date.
+///
+/// An unparsable date is ignored, as required by the RFC.
+pub fn not_modified_since(header: &str, last_modified: &DateTime) -> bool {
+    match DateTime::from_str(header.trim(), DateTimeFormat::HttpDate) {
+        Ok(since) => last_modified.secs() <= since.secs(),
+        Err(_) => false,
+    }
+}
+
+/// Whether an `If-Range` header still matches the representation, in which case the `Range`
+/// header must be honoured. Entity tags use the strong comparison and weak tags never match.
+pub fn if_range_matches(header: &str, etag: Option<&str>, last_modified: Option<&DateTime>) -> bool {
+    let header = header.trim();
+    if header.starts_with('"') || header.starts_with("W/") {
+        return match etag {
+            Some(etag) => !header.starts_with("W/") && !etag.starts_with("W/") && header == etag,
+            None => false,
+        };
+    }
+
+    match (DateTime::from_str(header, DateTimeFormat::HttpDate), last_modified) {
+        (Ok(date), Some(last_modified)) => date.secs() == last_modified.secs(),
+        _ => false,
+    }
+}
+
+fn strip_weak(etag: &str) -> &str {
+    etag.strip_prefix("W/").unwrap_or(etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETAG: &str = "\"5d41402abc4b2a76b9719d911017c592\"";
+    const LAST_MODIFIED: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+    fn last_modified() -> DateTime {
+        DateTime::from_str(LAST_MODIFIED, DateTimeFormat::HttpDate).unwrap()
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        assert!(if_none_match_matches("*", ETAG));
+        assert!(if_none_match_matches(ETAG, ETAG));
+        assert!(if_none_match_matches(&format!("\"other\", W/{}", ETAG), ETAG));
+        assert!(!if_none_match_matches("\"other\"", ETAG));
+    }
+
+    #[test]
+    fn test_not_modified_since() {
+        assert!(not_modified_since(LAST_MODIFIED, &last_modified()));
+        assert!(not_modified_since("Thu, 22 Oct 2015 07:28:00 GMT", &last_modified()));
+        assert!(!not_modified_since("Tue, 20 Oct 2015 07:28:00 GMT", &last_modified()));
+        assert!(!not_modified_since("yesterday", &last_modified()));
+    }
+
+    #[test]
+    fn test_if_range() {
+        assert!(if_range_matches(ETAG, Some(ETAG), None));
+        assert!(!if_range_matches(&format!("W/{}", ETAG), Some(ETAG), None));
+        assert!(!if_range_matches("\"other\"", Some(ETAG), None));
+        assert!(if_range_matches(LAST_MODIFIED, None, Some(&last_modified())));
+        assert!(!if_range_matches("Thu, 22 Oct 2015 07:28:00 GMT", None, Some(&last_modified())));
+    }
+}
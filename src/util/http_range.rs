@@ -0,0 +1,189 @@
+//! Parsing of the HTTP `Range` request header
+//!
+//! Only the `bytes` unit is supported, following
+//! [RFC 9110 section 14](https://www.rfc-editor.org/rfc/rfc9110#section-14).
+
+/// Maximum number of ranges honoured in a single request. Requests asking for more are served
+/// in full rather than fanning out into many object store calls.
+pub const MAX_RANGES: usize = 16;
+
+/// An inclusive byte range resolved against the length of the representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Value of the `Range` header passed through to the object store
+    pub fn to_range_header(self) -> String {
+        format!("bytes={}-{}", self.start, self.end)
+    }
+
+    /// Value of the `Content-Range` response header
+    pub fn to_content_range(self, total_len: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total_len)
+    }
+}
+
+/// Outcome of parsing a `Range` header that could not be turned into byte ranges
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeError {
+    /// The header is syntactically invalid, uses another unit or asks for too many ranges.
+    /// The header must be ignored and the full representation served.
+    Ignored,
+    /// None of the ranges overlap the representation, a `416` must be returned.
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header value against a representation of `total_len` bytes.
+///
+/// The returned ranges are sorted, clamped to the representation and coalesced when they
+/// overlap or are adjacent.
+pub fn parse_range_header(value: &str, total_len: u64) -> Result<Vec<ByteRange>, RangeError> {
+    let specs = match value.trim().strip_prefix("bytes=") {
+        Some(specs) => specs,
+        None => return Err(RangeError::Ignored),
+    };
+
+    let mut ranges = Vec::new();
+    let mut spec_count = 0;
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        spec_count += 1;
+        if spec_count > MAX_RANGES {
+            return Err(RangeError::Ignored);
+        }
+        if let Some(range) = parse_range_spec(spec, total_len)? {
+            ranges.push(range);
+        }
+    }
+
+    if spec_count == 0 {
+        return Err(RangeError::Ignored);
+    }
+    if ranges.is_empty() {
+        return Err(RangeError::Unsatisfiable);
+    }
+
+    Ok(coalesce(ranges))
+}
+
+/// Parse a single `first-last`, `first-` or `-suffix` spec.
+///
+/// Returns `Ok(None)` for a valid spec which does not overlap the representation.
+fn parse_range_spec(spec: &str, total_len: u64) -> Result<Option<ByteRange>, RangeError> {
+    let (first, last) = spec.split_once('-').ok_or(RangeError::Ignored)?;
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        let suffix: u64 = last.parse().map_err(|_| RangeError::Ignored)?;
+        if suffix == 0 || total_len == 0 {
+            return Ok(None);
+        }
+        return Ok(Some(ByteRange {
+            start: total_len.saturating_sub(suffix),
+            end: total_len - 1,
+        }));
+    }
+
+    let start: u64 = first.parse().map_err(|_| RangeError::Ignored)?;
+    let end = match last {
+        "" => None,
+        last => Some(last.parse::<u64>().map_err(|_| RangeError::Ignored)?),
+    };
+    if matches!(end, Some(end) if end < start) {
+        return Err(RangeError::Ignored);
+    }
+    if start >= total_len {
+        return Ok(None);
+    }
+
+    Ok(Some(ByteRange {
+        start,
+        end: end.map_or(total_len - 1, |end| end.min(total_len - 1)),
+    }))
+}
+
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn test_parse_single_range() {
+        assert_eq!(parse_range_header("bytes=0-499", 1000), Ok(vec![range(0, 499)]));
+        assert_eq!(parse_range_header("bytes=500-", 1000), Ok(vec![range(500, 999)]));
+        assert_eq!(parse_range_header("bytes=-200", 1000), Ok(vec![range(800, 999)]));
+        assert_eq!(parse_range_header("bytes=900-5000", 1000), Ok(vec![range(900, 999)]));
+        assert_eq!(parse_range_header("bytes=-5000", 1000), Ok(vec![range(0, 999)]));
+    }
+
+    #[test]
+    fn test_parse_multi_range() {
+        assert_eq!(
+            parse_range_header("bytes=0-99, 200-299,-100", 1000),
+            Ok(vec![range(0, 99), range(200, 299), range(900, 999)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=200-299,0-150,100-199", 1000),
+            Ok(vec![range(0, 299)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=0-9,10-19", 1000),
+            Ok(vec![range(0, 19)])
+        );
+    }
+
+    #[test]
+    fn test_parse_unsatisfiable() {
+        assert_eq!(parse_range_header("bytes=1000-", 1000), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse_range_header("bytes=-0", 1000), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse_range_header("bytes=0-10", 0), Err(RangeError::Unsatisfiable));
+        assert_eq!(parse_range_header("bytes=2000-2100,1500-", 1000), Err(RangeError::Unsatisfiable));
+    }
+
+    #[test]
+    fn test_parse_ignored() {
+        assert_eq!(parse_range_header("items=0-10", 1000), Err(RangeError::Ignored));
+        assert_eq!(parse_range_header("bytes=", 1000), Err(RangeError::Ignored));
+        assert_eq!(parse_range_header("bytes=10-5", 1000), Err(RangeError::Ignored));
+        assert_eq!(parse_range_header("bytes=a-b", 1000), Err(RangeError::Ignored));
+        assert_eq!(parse_range_header("bytes=10", 1000), Err(RangeError::Ignored));
+
+        let too_many = (0..=MAX_RANGES).map(|i| format!("{}-{}", i * 10, i * 10 + 1)).collect::<Vec<_>>();
+        assert_eq!(
+            parse_range_header(&format!("bytes={}", too_many.join(",")), 1000),
+            Err(RangeError::Ignored)
+        );
+    }
+
+    #[test]
+    fn test_header_values() {
+        let r = range(100, 199);
+        assert_eq!(r.len(), 100);
+        assert_eq!(r.to_range_header(), "bytes=100-199");
+        assert_eq!(r.to_content_range(1000), "bytes 100-199/1000");
+    }
+}
@@ -0,0 +1,226 @@
+//! Encoding of ZIP archives whose entries are streamed without being buffered
+//!
+//! Entries are `STORED` (uncompressed) and followed by a data descriptor, so the CRC-32 and
+//! sizes can be computed while the content is written. ZIP64 is not supported: entries and
+//! the whole archive must stay below 4 GiB.
+//!
+//! See the [ZIP specification](https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT).
+
+use bytes::{BufMut, Bytes, BytesMut};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+/// Largest archive that can be written without ZIP64
+pub const MAX_ARCHIVE_SIZE: u64 = u32::MAX as u64;
+/// Longest entry name, in bytes, that the headers can record
+pub const MAX_ENTRY_NAME_LEN: usize = u16::MAX as usize;
+
+const LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+
+const VERSION: u16 = 20;
+/// Sizes and CRC-32 are in the data descriptor, the name is UTF-8
+const FLAGS: u16 = 0x0008 | 0x0800;
+const METHOD_STORED: u16 = 0;
+
+/// An entry whose content has been written, as recorded in the central directory
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    pub name: String,
+    pub modified: DateTime<Utc>,
+    pub offset: u32,
+    pub crc32: u32,
+    pub size: u32,
+}
+
+/// Writes the framing of an archive and keeps track of the entries and offsets.
+///
+/// For every entry call [`start_entry`](ZipWriter::start_entry), emit the content while feeding
+/// it to a [`crc32fast::Hasher`], then call [`finish_entry`](ZipWriter::finish_entry).
+/// [`finish`](ZipWriter::finish) returns the central directory.
+#[derive(Debug, Default)]
+pub struct ZipWriter {
+    entries: Vec<ZipEntry>,
+    offset: u64,
+    current: Option<(String, DateTime<Utc>, u64)>,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        ZipWriter::default()
+    }
+
+    /// Number of bytes emitted so far
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Local file header of a new entry, whose name must be at most
+    /// [`MAX_ENTRY_NAME_LEN`] bytes long
+    pub fn start_entry(&mut self, name: &str, modified: DateTime<Utc>) -> Bytes {
+        let name_len = u16::try_from(name.len()).expect("the entry name is longer than the ZIP headers allow");
+        let (time, date) = dos_date_time(&modified);
+        let mut buf = BytesMut::with_capacity(30 + name.len());
+        buf.put_u32_le(LOCAL_FILE_HEADER_SIGNATURE);
+        buf.put_u16_le(VERSION);
+        buf.put_u16_le(FLAGS);
+        buf.put_u16_le(METHOD_STORED);
+        buf.put_u16_le(time);
+        buf.put_u16_le(date);
+        buf.put_u32_le(0); // crc-32, in the data descriptor
+        buf.put_u32_le(0); // compressed size, in the data descriptor
+        buf.put_u32_le(0); // uncompressed size, in the data descriptor
+        buf.put_u16_le(name_len);
+        buf.put_u16_le(0); // extra field length
+        buf.put_slice(name.as_bytes());
+
+        self.current = Some((name.to_string(), modified, self.offset));
+        self.offset += buf.len() as u64;
+        buf.freeze()
+    }
+
+    /// Data descriptor closing the current entry, once its `size` bytes of content were emitted
+    pub fn finish_entry(&mut self, crc32: u32, size: u64) -> Bytes {
+        let (name, modified, offset) = self.current.take().expect("no entry was started");
+        self.offset += size;
+
+        let mut buf = BytesMut::with_capacity(16);
+        buf.put_u32_le(DATA_DESCRIPTOR_SIGNATURE);
+        buf.put_u32_le(crc32);
+        buf.put_u32_le(size as u32);
+        buf.put_u32_le(size as u32);
+        self.offset += buf.len() as u64;
+
+        self.entries.push(ZipEntry {
+            name,
+            modified,
+            offset: offset as u32,
+            crc32,
+            size: size as u32,
+        });
+        buf.freeze()
+    }
+
+    /// Central directory and end of central directory record
+    pub fn finish(self) -> Bytes {
+        let mut buf = BytesMut::new();
+        for entry in &self.entries {
+            let (time, date) = dos_date_time(&entry.modified);
+            buf.put_u32_le(CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+            buf.put_u16_le(VERSION); // version made by
+            buf.put_u16_le(VERSION); // version needed to extract
+            buf.put_u16_le(FLAGS);
+            buf.put_u16_le(METHOD_STORED);
+            buf.put_u16_le(time);
+            buf.put_u16_le(date);
+            buf.put_u32_le(entry.crc32);
+            buf.put_u32_le(entry.size);
+            buf.put_u32_le(entry.size);
+            buf.put_u16_le(entry.name.len() as u16); // checked by start_entry
+            buf.put_u16_le(0); // extra field length
+            buf.put_u16_le(0); // comment length
+            buf.put_u16_le(0); // disk number start
+            buf.put_u16_le(0); // internal attributes
+            buf.put_u32_le(0); // external attributes
+            buf.put_u32_le(entry.offset);
+            buf.put_slice(entry.name.as_bytes());
+        }
+
+        let directory_size = buf.len() as u32;
+        buf.put_u32_le(END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        buf.put_u16_le(0); // number of this disk
+        buf.put_u16_le(0); // disk where the central directory starts
+        buf.put_u16_le(self.entries.len() as u16);
+        buf.put_u16_le(self.entries.len() as u16);
+        buf.put_u32_le(directory_size);
+        buf.put_u32_le(self.offset as u32);
+        buf.put_u16_le(0); // comment length
+        buf.freeze()
+    }
+}
+
+/// Size of the framing added around entries with the given name lengths
+pub fn framing_size(name_lengths: impl IntoIterator<Item = usize>) -> u64 {
+    let per_entry: u64 = name_lengths
+        .into_iter()
+        .map(|len| 30 + 16 + 46 + 2 * len as u64)
+        .sum();
+    per_entry + 22
+}
+
+/// MS-DOS `(time, date)` of a timestamp, clamped to the 1980 epoch of the format
+fn dos_date_time(timestamp: &DateTime<Utc>) -> (u16, u16) {
+    if timestamp.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (timestamp.hour() << 11) | (timestamp.minute() << 5) | (timestamp.second() / 2);
+    let date = (((timestamp.year() - 1980) as u32) << 9) | (timestamp.month() << 5) | timestamp.day();
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2023-11-21T10:30:44Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn write_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new();
+        let mut archive = Vec::new();
+        for (name, content) in entries {
+            archive.extend_from_slice(&writer.start_entry(name, timestamp()));
+            archive.extend_from_slice(content);
+            archive.extend_from_slice(&writer.finish_entry(crc32fast::hash(content), content.len() as u64));
+        }
+        assert_eq!(writer.offset(), archive.len() as u64);
+        archive.extend_from_slice(&writer.finish());
+        archive
+    }
+
+    fn u32_at(buf: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u16_at(buf: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_dos_date_time() {
+        assert_eq!(dos_date_time(&timestamp()), ((10 << 11) | (30 << 5) | 22, (43 << 9) | (11 << 5) | 21));
+    }
+
+    #[test]
+    fn test_archive_layout() {
+        let archive = write_archive(&[("a.txt", b"hello"), ("b.txt", b"world!")]);
+        assert_eq!(archive.len() as u64, framing_size([5, 5]) + 11);
+
+        assert_eq!(u32_at(&archive, 0), LOCAL_FILE_HEADER_SIGNATURE);
+        assert_eq!(&archive[30..35], b"a.txt");
+        assert_eq!(&archive[35..40], b"hello");
+        assert_eq!(u32_at(&archive, 40), DATA_DESCRIPTOR_SIGNATURE);
+        assert_eq!(u32_at(&archive, 44), crc32fast::hash(b"hello"));
+        assert_eq!(u32_at(&archive, 48), 5);
+
+        let second = 56;
+        assert_eq!(u32_at(&archive, second), LOCAL_FILE_HEADER_SIGNATURE);
+
+        let eocd = archive.len() - 22;
+        assert_eq!(u32_at(&archive, eocd), END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        assert_eq!(u16_at(&archive, eocd + 10), 2);
+        let directory_size = u32_at(&archive, eocd + 12) as usize;
+        let directory_offset = u32_at(&archive, eocd + 16) as usize;
+        assert_eq!(directory_offset + directory_size, eocd);
+
+        assert_eq!(u32_at(&archive, directory_offset), CENTRAL_DIRECTORY_HEADER_SIGNATURE);
+        assert_eq!(u32_at(&archive, directory_offset + 16), crc32fast::hash(b"hello"));
+        assert_eq!(u32_at(&archive, directory_offset + 42), 0);
+        let second_record = directory_offset + 46 + 5;
+        assert_eq!(u32_at(&archive, second_record + 42), second as u32);
+    }
+}
@@ -0,0 +1,134 @@
+use std::env;
+use std::sync::Arc;
+
+use anyhow::Context;
+use log::{error, info};
+use tokio::sync::Semaphore;
+
+use crate::constants::variant_constants::*;
+use crate::logging::error_fields::with_error_fields;
+use crate::s3_client::client::S3Client;
+use crate::variants::render::{decode_source, render_variant};
+use crate::variants::spec::{parse_variant_specs, VariantSpec};
+use crate::util::env::env_parse;
+
+pub mod orientation;
+pub mod render;
+pub mod spec;
+
+/// Content types derivatives can be generated from
+const SUPPORTED_CONTENT_TYPES: [&str; 6] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/bmp",
+    "image/tiff",
+];
+
+/// Generates the configured derivatives of uploaded images in the background and stores them
+/// next to the original under `{file_id}/variants/{name}`.
+#[derive(Debug, Clone)]
+pub struct VariantGenerator {
+    s3_client: S3Client,
+    specs: Arc<Vec<VariantSpec>>,
+    max_source_bytes: u64,
+    permits: Arc<Semaphore>,
+}
+
+impl VariantGenerator {
+    /// Read the derivative specifications from `IMAGE_VARIANTS`.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the specifications are invalid, so a misconfigured deployment fails at startup.
+    pub fn from_env(s3_client: S3Client) -> Self {
+        let specs = env::var(IMAGE_VARIANTS_ENV).unwrap_or(DEFAULT_IMAGE_VARIANTS.to_string());
+        let specs = parse_variant_specs(&specs).unwrap_or_else(|err| panic!("{}", err));
+        let max_source_bytes = env_parse(IMAGE_VARIANT_MAX_SOURCE_BYTES_ENV, DEFAULT_IMAGE_VARIANT_MAX_SOURCE_BYTES);
+        let concurrency = env_parse(IMAGE_VARIANT_CONCURRENCY_ENV, DEFAULT_IMAGE_VARIANT_CONCURRENCY).max(1);
+
+        VariantGenerator {
+            s3_client,
+            specs: Arc::new(specs),
+            max_source_bytes,
+            permits: Arc::new(Semaphore::new(concurrency)),
+        }
+    }
+
+    pub fn specs(&self) -> &[VariantSpec] {
+        &self.specs
+    }
+
+    pub fn spec(&self, name: &str) -> Option<&VariantSpec> {
+        self.specs.iter().find(|spec| spec.name == name)
+    }
+
+    /// Object key of the `name` derivative of `file_id`
+    pub fn variant_key(file_id: &str, name: &str) -> String {
+        format!("{}/variants/{}", file_id, name)
+    }
+
+    /// Whether derivatives are generated for an upload of `content_type` and `size` bytes
+    pub fn applies_to(&self, content_type: &str, size: u64) -> bool {
+        !self.specs.is_empty()
+            && size <= self.max_source_bytes
+            && SUPPORTED_CONTENT_TYPES.contains(&content_type)
+    }
+
+    /// Generate the derivatives of `file_id` in a background task
+    pub fn spawn(&self, file_id: String) {
+        let generator = self.clone();
+        tokio::spawn(async move {
+            match generator.generate(&file_id).await {
+                Ok(()) => info!("generated {} image variants of file {}", generator.specs.len(), file_id),
+                Err(err) => with_error_fields(&err, || {
+                    error!("failed to generate image variants of file {}: {:#}", file_id, err)
+                }),
+            }
+        });
+    }
+
+    async fn generate(&self, file_id: &str) -> anyhow::Result<()> {
+        let _permit = self.permits.acquire().await?;
+
+        let output = self
+            .s3_client
+            .get_object(file_id, None, None)
+            .await
+            .context("failed to download the original")?;
+        let source = output.body.collect().await?.into_bytes();
+
+        let specs = self.specs.clone();
+        let rendered = tokio::task::spawn_blocking(move || -> anyhow::Result<Vec<(VariantSpec, Vec<u8>)>> {
+            let image = decode_source(&source).context("failed to decode the original")?;
+            specs
+                .iter()
+                .map(|spec| Ok((spec.clone(), render_variant(&image, spec)?)))
+                .collect()
+        })
+        .await??;
+
+        for (spec, bytes) in rendered {
+            self.s3_client
+                .put_object_bytes(
+                    &Self::variant_key(file_id, &spec.name),
+                    bytes,
+                    spec.format.content_type(),
+                )
+                .await
+                .with_context(|| format!("failed to store the {} variant", spec.name))?;
+        }
+        Ok(())
+    }
+
+    /// Delete every derivative of `file_id`, logging the ones that could not be removed
+    pub async fn delete_variants(&self, file_id: &str) {
+        for spec in self.specs.iter() {
+            let key = Self::variant_key(file_id, &spec.name);
+            if let Err(err) = self.s3_client.delete_object(&key).await {
+                error!("failed to delete image variant {}: {}", key, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! EXIF orientation of the uploaded images
+//!
+//! Cameras store the pixels as captured and record how to display them in the EXIF
+//! `Orientation` tag. The decoder ignores the tag and re-encoding drops it, so it is applied to
+//! the pixels before rendering the derivatives. Only the tag is read, from the EXIF block of JPEG,
+//! PNG and WebP files or the first directory of TIFF files.
+
+use image::DynamicImage;
+
+const ORIENTATION_TAG: u16 = 0x0112;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Value of the EXIF `Orientation` tag of an encoded image, from 1 to 8
+pub fn exif_orientation(bytes: &[u8]) -> Option<u16> {
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        jpeg_exif(bytes).and_then(tiff_orientation)
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        png_exif(bytes).and_then(tiff_orientation)
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        webp_exif(bytes).and_then(tiff_orientation)
+    } else {
+        tiff_orientation(bytes)
+    }
+}
+
+/// Rotate and flip `image` so it displays upright once the `orientation` is dropped
+pub fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// TIFF structure of the APP1 `Exif` segment, which precedes the image data
+fn jpeg_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    loop {
+        if *bytes.get(pos)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(pos + 1)?;
+        match marker {
+            // fill byte
+            0xFF => pos += 1,
+            // markers without a length
+            0x01 | 0xD0..=0xD7 => pos += 2,
+            // start of scan, or end of image: no EXIF before the image data
+            0xDA | 0xD9 => return None,
+            _ => {
+                let len = usize::from(u16::from_be_bytes(bytes.get(pos + 2..pos + 4)?.try_into().ok()?));
+                let data = bytes.get(pos + 4..pos + 2 + len)?;
+                if marker == 0xE1 && data.starts_with(EXIF_HEADER) {
+                    return Some(&data[EXIF_HEADER.len()..]);
+                }
+                pos += 2 + len;
+            }
+        }
+    }
+}
+
+/// Content of the `eXIf` chunk
+fn png_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = PNG_SIGNATURE.len();
+    loop {
+        let len = u32::from_be_bytes(bytes.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let kind = bytes.get(pos + 4..pos + 8)?;
+        let data = bytes.get(pos + 8..(pos + 8).checked_add(len)?)?;
+        match kind {
+            b"eXIf" => return Some(data),
+            b"IEND" => return None,
+            _ => pos += 12 + len,
+        }
+    }
+}
+
+/// Content of the `EXIF` chunk, which some writers start with the JPEG `Exif` header
+fn webp_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = 12;
+    loop {
+        let kind = bytes.get(pos..pos + 4)?;
+        let len = u32::from_le_bytes(bytes.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        let data = bytes.get(pos + 8..(pos + 8).checked_add(len)?)?;
+        if kind == b"EXIF" {
+            return Some(data.strip_prefix(EXIF_HEADER).unwrap_or(data));
+        }
+        // chunks are padded to an even size
+        pos += 8 + len + len % 2;
+    }
+}
+
+/// `Orientation` entry of the first image file directory of a TIFF structure
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let bytes = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let bytes = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entries = usize::from(u16_at(ifd)?);
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        // a SHORT, stored in the first bytes of the value field
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// TIFF structure holding only an `Orientation` entry
+#[cfg(test)]
+pub(crate) fn tiff_with_orientation(orientation: u16, big_endian: bool) -> Vec<u8> {
+    let u16_bytes = |v: u16| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+    let u32_bytes = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+
+    let mut tiff = if big_endian { b"MM\0*".to_vec() } else { b"II*\0".to_vec() };
+    tiff.extend(u32_bytes(8));
+    tiff.extend(u16_bytes(1));
+    tiff.extend(u16_bytes(ORIENTATION_TAG));
+    tiff.extend(u16_bytes(3)); // SHORT
+    tiff.extend(u32_bytes(1));
+    tiff.extend(u16_bytes(orientation));
+    tiff.extend([0, 0]);
+    tiff.extend(u32_bytes(0)); // no next directory
+    tiff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    #[test]
+    fn test_tiff_orientation() {
+        assert_eq!(tiff_orientation(&tiff_with_orientation(6, true)), Some(6));
+        assert_eq!(tiff_orientation(&tiff_with_orientation(8, false)), Some(8));
+        assert_eq!(tiff_orientation(&tiff_with_orientation(9, false)), None);
+        assert_eq!(tiff_orientation(&tiff_with_orientation(6, true)[..12]), None);
+    }
+
+    #[test]
+    fn test_png_exif() {
+        let data = tiff_with_orientation(3, false);
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend((data.len() as u32).to_be_bytes());
+        png.extend(b"eXIf");
+        png.extend(&data);
+        png.extend([0; 4]); // crc, not checked
+        assert_eq!(exif_orientation(&png), Some(3));
+    }
+
+    #[test]
+    fn test_webp_exif() {
+        let data = [EXIF_HEADER, &tiff_with_orientation(5, true)].concat();
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X".to_vec();
+        webp.extend(10u32.to_le_bytes());
+        webp.extend([0; 10]);
+        webp.extend(b"EXIF");
+        webp.extend((data.len() as u32).to_le_bytes());
+        webp.extend(&data);
+        assert_eq!(exif_orientation(&webp), Some(5));
+    }
+
+    #[test]
+    fn test_apply_orientation() {
+        // red top-left pixel of a 2x1 image
+        let mut pixels = RgbaImage::from_pixel(2, 1, Rgba([0, 0, 255, 255]));
+        pixels.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let image = DynamicImage::ImageRgba8(pixels);
+        let red = Rgba([255, 0, 0, 255]);
+
+        let expected = [
+            (1, (0, 0)),
+            (2, (1, 0)),
+            (3, (1, 0)),
+            (4, (0, 0)),
+            (5, (0, 0)),
+            (6, (0, 0)),
+            (7, (0, 1)),
+            (8, (0, 1)),
+        ];
+        for (orientation, (x, y)) in expected {
+            let oriented = apply_orientation(image.clone(), orientation);
+            let size = if orientation >= 5 { (1, 2) } else { (2, 1) };
+            assert_eq!(oriented.dimensions(), size, "orientation {}", orientation);
+            assert_eq!(oriented.get_pixel(x, y), red, "orientation {}", orientation);
+        }
+    }
+}
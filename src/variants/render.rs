@@ -0,0 +1,115 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageResult};
+
+use crate::constants::variant_constants::JPEG_QUALITY;
+use crate::variants::orientation::{apply_orientation, exif_orientation};
+use crate::variants::spec::{VariantFormat, VariantSpec};
+
+/// Decode an uploaded image, turned upright according to its EXIF orientation
+pub fn decode_source(bytes: &[u8]) -> ImageResult<DynamicImage> {
+    let image = image::load_from_memory(bytes)?;
+    Ok(match exif_orientation(bytes) {
+        Some(orientation) => apply_orientation(image, orientation),
+        None => image,
+    })
+}
+
+/// Render a derivative of `image`.
+///
+/// The image is only scaled down, never up. Re-encoding drops the EXIF and other metadata
+/// of the original, so `image` must already be upright; see [`decode_source`].
+pub fn render_variant(image: &DynamicImage, spec: &VariantSpec) -> ImageResult<Vec<u8>> {
+    let resized;
+    let image = match spec.max_size {
+        Some((width, height)) if image.width() > width || image.height() > height => {
+            resized = image.resize(width, height, FilterType::Lanczos3);
+            &resized
+        }
+        _ => image,
+    };
+
+    let mut buf = Cursor::new(Vec::new());
+    match spec.format {
+        // JPEG has no alpha channel
+        VariantFormat::Jpeg => {
+            let rgb = image.to_rgb8();
+            JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY).encode_image(&rgb)?;
+        }
+        VariantFormat::Png | VariantFormat::WebP => {
+            image.write_to(&mut buf, spec.format.image_format())?;
+        }
+    }
+    Ok(buf.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::orientation::tiff_with_orientation;
+    use image::{GenericImageView, ImageFormat, RgbaImage};
+
+    fn source() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(400, 200, image::Rgba([200, 10, 10, 255])))
+    }
+
+    fn spec(max_size: Option<(u32, u32)>, format: VariantFormat) -> VariantSpec {
+        VariantSpec {
+            name: "test".to_string(),
+            max_size,
+            format,
+        }
+    }
+
+    #[test]
+    fn test_render_thumbnail() {
+        let encoded = render_variant(&source(), &spec(Some((128, 128)), VariantFormat::WebP)).unwrap();
+        assert_eq!(image::guess_format(&encoded).unwrap(), ImageFormat::WebP);
+        assert_eq!(image::load_from_memory(&encoded).unwrap().dimensions(), (128, 64));
+    }
+
+    #[test]
+    fn test_render_does_not_upscale() {
+        let encoded = render_variant(&source(), &spec(Some((1000, 1000)), VariantFormat::Jpeg)).unwrap();
+        assert_eq!(image::guess_format(&encoded).unwrap(), ImageFormat::Jpeg);
+        assert_eq!(image::load_from_memory(&encoded).unwrap().dimensions(), (400, 200));
+    }
+
+    #[test]
+    fn test_decode_source_applies_orientation() {
+        // red left half and blue right half, as stored by a camera held in portrait
+        let mut pixels = image::RgbImage::from_pixel(16, 8, image::Rgb([0, 0, 255]));
+        for x in 0..8 {
+            for y in 0..8 {
+                pixels.put_pixel(x, y, image::Rgb([255, 0, 0]));
+            }
+        }
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 100).encode_image(&pixels).unwrap();
+
+        // APP1 segment with orientation 6, displayed rotated 90° clockwise
+        let exif = [b"Exif\0\0".as_slice(), &tiff_with_orientation(6, true)].concat();
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend((exif.len() as u16 + 2).to_be_bytes());
+        segment.extend(exif);
+        jpeg.splice(2..2, segment);
+
+        let image = decode_source(&jpeg).unwrap();
+        assert_eq!(image.dimensions(), (8, 16));
+        let (top, bottom) = (image.get_pixel(4, 4), image.get_pixel(4, 12));
+        assert!(top[0] > 200 && top[2] < 50, "top is {:?}", top);
+        assert!(bottom[2] > 200 && bottom[0] < 50, "bottom is {:?}", bottom);
+
+        let encoded = render_variant(&image, &spec(Some((4, 4)), VariantFormat::WebP)).unwrap();
+        assert_eq!(image::load_from_memory(&encoded).unwrap().dimensions(), (2, 4));
+    }
+
+    #[test]
+    fn test_render_conversion_only() {
+        let encoded = render_variant(&source(), &spec(None, VariantFormat::Png)).unwrap();
+        assert_eq!(image::guess_format(&encoded).unwrap(), ImageFormat::Png);
+        assert_eq!(image::load_from_memory(&encoded).unwrap().dimensions(), (400, 200));
+    }
+}
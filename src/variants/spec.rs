@@ -0,0 +1,131 @@
+use std::fmt;
+
+use image::ImageFormat;
+
+/// Encoding of a derivative
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantFormat {
+    Jpeg,
+    Png,
+    WebP,
+}
+
+impl VariantFormat {
+    pub fn from_str(format: &str) -> Option<VariantFormat> {
+        match format.to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(VariantFormat::Jpeg),
+            "png" => Some(VariantFormat::Png),
+            "webp" => Some(VariantFormat::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        self.image_format().to_mime_type()
+    }
+
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            VariantFormat::Jpeg => ImageFormat::Jpeg,
+            VariantFormat::Png => ImageFormat::Png,
+            VariantFormat::WebP => ImageFormat::WebP,
+        }
+    }
+}
+
+/// A derivative generated for every uploaded image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariantSpec {
+    pub name: String,
+    /// Bounding box the image is scaled down to, keeping its aspect ratio. `None` only
+    /// re-encodes the image.
+    pub max_size: Option<(u32, u32)>,
+    pub format: VariantFormat,
+}
+
+/// Error returned by [`parse_variant_specs`]
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidVariantSpec(pub String);
+
+impl fmt::Display for InvalidVariantSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid image variant specification: {}", self.0)
+    }
+}
+
+/// Parse comma separated `name:WIDTHxHEIGHT:format` or `name:format` specifications
+pub fn parse_variant_specs(value: &str) -> Result<Vec<VariantSpec>, InvalidVariantSpec> {
+    let mut specs: Vec<VariantSpec> = Vec::new();
+    for spec in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let invalid = || InvalidVariantSpec(spec.to_string());
+        let parts: Vec<&str> = spec.split(':').map(str::trim).collect();
+
+        let (name, max_size, format) = match parts.as_slice() {
+            [name, size, format] => (*name, Some(parse_size(size).ok_or_else(invalid)?), *format),
+            [name, format] => (*name, None, *format),
+            _ => return Err(invalid()),
+        };
+        let valid_name = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name || specs.iter().any(|s| s.name == name) {
+            return Err(invalid());
+        }
+
+        specs.push(VariantSpec {
+            name: name.to_string(),
+            max_size,
+            format: VariantFormat::from_str(format).ok_or_else(invalid)?,
+        });
+    }
+    Ok(specs)
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_variant_specs() {
+        assert_eq!(
+            parse_variant_specs("thumbnail:128x96:webp, original:JPG").unwrap(),
+            vec![
+                VariantSpec {
+                    name: "thumbnail".to_string(),
+                    max_size: Some((128, 96)),
+                    format: VariantFormat::WebP,
+                },
+                VariantSpec {
+                    name: "original".to_string(),
+                    max_size: None,
+                    format: VariantFormat::Jpeg,
+                },
+            ]
+        );
+        assert_eq!(parse_variant_specs("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_invalid_variant_specs() {
+        assert!(parse_variant_specs("thumbnail:128:webp").is_err());
+        assert!(parse_variant_specs("thumbnail:0x10:webp").is_err());
+        assert!(parse_variant_specs("thumbnail:128x128:avif").is_err());
+        assert!(parse_variant_specs("../x:png").is_err());
+        assert!(parse_variant_specs("a:png,a:webp").is_err());
+        assert!(parse_variant_specs("a").is_err());
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(VariantFormat::WebP.content_type(), "image/webp");
+        assert_eq!(VariantFormat::Jpeg.content_type(), "image/jpeg");
+    }
+}